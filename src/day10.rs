@@ -64,6 +64,139 @@ fn cpu_iter(ops: &[Operation]) -> impl Iterator<Item = CPUState> {
     }
 }
 
+#[derive(Display, Debug, Clone, Eq, PartialEq)]
+pub enum AsmError {
+    #[display("line {0}: unknown instruction `{1}`")]
+    UnknownInstruction(usize, String),
+    #[display("line {0}: invalid repeat count in `{1}`")]
+    InvalidRepeat(usize, String),
+    #[display("line {0}: invalid label `{1}`")]
+    InvalidLabel(usize, String),
+    #[display("line {0}: duplicate label `{1}`")]
+    DuplicateLabel(usize, String),
+}
+
+/// An assembled program along with the op index each label points at
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Program {
+    pub ops: Vec<Operation>,
+    pub labels: HashMap<String, usize>,
+}
+
+/// Assembles source with `name:` labels, `;`/`#` comments and `repeat N <stmt>` macros
+pub fn assemble(src: &str) -> Result<Program, AsmError> {
+    let mut program = Program::default();
+    for (idx, line) in src.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut stmt = line.split([';', '#']).next().unwrap().trim();
+        if let Some((label, rest)) = stmt.split_once(':') {
+            let label = label.trim();
+            if label.is_empty() || !label.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(AsmError::InvalidLabel(line_no, label.to_string()));
+            }
+            if program
+                .labels
+                .insert(label.to_string(), program.ops.len())
+                .is_some()
+            {
+                return Err(AsmError::DuplicateLabel(line_no, label.to_string()));
+            }
+            stmt = rest.trim();
+        }
+        if !stmt.is_empty() {
+            program.ops.extend(assemble_statement(line_no, stmt)?);
+        }
+    }
+    Ok(program)
+}
+
+fn assemble_statement(line: usize, stmt: &str) -> Result<Vec<Operation>, AsmError> {
+    match stmt.split_once(char::is_whitespace) {
+        Some(("repeat", rest)) => {
+            let (count, body) = rest
+                .trim_start()
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| AsmError::InvalidRepeat(line, stmt.to_string()))?;
+            let count = count
+                .parse::<usize>()
+                .map_err(|_| AsmError::InvalidRepeat(line, stmt.to_string()))?;
+            Ok(assemble_statement(line, body.trim())?.repeat(count))
+        }
+        _ => stmt
+            .split_whitespace()
+            .join(" ")
+            .parse()
+            .map(|op| vec![op])
+            .map_err(|_| AsmError::UnknownInstruction(line, stmt.to_string())),
+    }
+}
+
+/// Prints ops back out in the canonical puzzle format, one per line
+pub fn disassemble(ops: &[Operation]) -> String {
+    ops.iter().map(Operation::to_string).join("\n")
+}
+
+const CRT_WIDTH: usize = 40;
+const CRT_HEIGHT: usize = 6;
+const CRT_CYCLES: usize = CRT_WIDTH * CRT_HEIGHT;
+// register values outside this range never light a pixel so they're interchangeable
+const X_MIN: i64 = -2;
+const X_MAX: i64 = CRT_WIDTH as i64 + 1;
+
+pub type Image = [[bool; CRT_WIDTH]; CRT_HEIGHT];
+
+/// Parses a 40x6 drawing where `#` is lit and anything else is dark
+pub fn parse_image(s: &str) -> Image {
+    let mut image = [[false; CRT_WIDTH]; CRT_HEIGHT];
+    for (row, line) in s.lines().take(CRT_HEIGHT).enumerate() {
+        for (col, chr) in line.chars().take(CRT_WIDTH).enumerate() {
+            image[row][col] = chr == '#';
+        }
+    }
+    image
+}
+
+/// Finds a program that draws `image` in exactly 240 cycles, or None if it can't be drawn
+pub fn synthesize(image: &Image) -> Option<Vec<Operation>> {
+    let draws = |cycle: usize, x: i64| {
+        let col = cycle % CRT_WIDTH;
+        ((x - col as i64).abs() <= 1) == image[cycle / CRT_WIDTH][col]
+    };
+    let idx = |x: i64| (x - X_MIN) as usize;
+
+    // finishes[cycle][x] is whether the rest of the image can be drawn from cycle with register x
+    let mut finishes = vec![vec![false; idx(X_MAX) + 1]; CRT_CYCLES + 1];
+    finishes[CRT_CYCLES].fill(true);
+    for cycle in (0..CRT_CYCLES).rev() {
+        let addx_lands = cycle + 2 <= CRT_CYCLES && finishes[cycle + 2].contains(&true);
+        for x in X_MIN..=X_MAX {
+            finishes[cycle][idx(x)] = draws(cycle, x)
+                && (finishes[cycle + 1][idx(x)] || (addx_lands && draws(cycle + 1, x)));
+        }
+    }
+
+    let mut x = 1;
+    if !finishes[0][idx(x)] {
+        return None;
+    }
+    let mut ops = vec![];
+    let mut cycle = 0;
+    while cycle < CRT_CYCLES {
+        if finishes[cycle + 1][idx(x)] {
+            ops.push(Operation::Noop);
+            cycle += 1;
+        } else {
+            let next = (X_MIN..=X_MAX)
+                .find(|next| finishes[cycle + 2][idx(*next)])
+                .unwrap();
+            ops.push(Operation::Addx(next - x));
+            x = next;
+            cycle += 2;
+        }
+    }
+    Some(ops)
+}
+
 /// https://adventofcode.com/2022/day/10
 #[aoc_generator(day10)]
 pub fn generator(input: &str) -> Vec<Operation> {
@@ -114,6 +247,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn assembler_round_trips() {
+        let src = "start: noop ; warm up
+# three cycles of nothing
+repeat 3 noop
+loop:
+    addx   5
+repeat 2 repeat 2 addx -1";
+        let program = assemble(src).unwrap();
+        assert_eq!(
+            disassemble(&program.ops),
+            "noop\nnoop\nnoop\nnoop\naddx 5\naddx -1\naddx -1\naddx -1\naddx -1"
+        );
+        assert_eq!(Some(&0), program.labels.get("start"));
+        assert_eq!(Some(&4), program.labels.get("loop"));
+        assert_eq!(EXAMPLE_INPUT, disassemble(&generator(EXAMPLE_INPUT)));
+        assert_eq!(generator(EXAMPLE_INPUT), assemble(EXAMPLE_INPUT).unwrap().ops);
+        assert_eq!(
+            Err(AsmError::UnknownInstruction(2, "jmp start".to_string())),
+            assemble("start:\njmp start")
+        );
+        assert_eq!(
+            Err(AsmError::InvalidRepeat(1, "repeat x noop".to_string())),
+            assemble("repeat x noop")
+        );
+    }
+
+    #[test]
+    fn synthesizes_images() {
+        let expected = "\n".to_string() + &EXAMPLE_INPUT_SOL.replace('#', "█").replace('.', " ");
+        let ops = synthesize(&parse_image(EXAMPLE_INPUT_SOL)).unwrap();
+        assert_eq!(expected, solve_part2(&ops));
+        assert_eq!(CRT_CYCLES, cpu_iter(&ops).count());
+
+        // the register sits at 1 until the end of the second cycle so those pixels are always lit
+        let mut image = parse_image("");
+        assert_eq!(None, synthesize(&image));
+        image[0][0] = true;
+        image[0][1] = true;
+        let drawn = solve_part2(&synthesize(&image).unwrap()).replace('\n', "");
+        assert_eq!("██".to_string() + &" ".repeat(CRT_CYCLES - 2), drawn);
+    }
+
     // #[test]
     // fn it_works_part2() {
     // assert_eq!(EXAMPLE_INPUT_SOL, solve_part2(&generator(EXAMPLE_INPUT)));
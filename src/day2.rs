@@ -1,111 +1,294 @@
-#[derive(Eq, PartialEq, Debug, Copy, Clone)]
-pub enum Shape {
-    Rock,
-    Paper,
-    Scissors,
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A shape in a cyclic game, numbered by its place around the circle
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Hash)]
+pub struct Shape(pub usize);
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Hash)]
+pub enum Outcome {
+    Lose,
+    Draw,
+    Win,
 }
 
-impl Shape {
-    fn points(&self) -> i64 {
-        match self {
-            Shape::Rock => 1,
-            Shape::Paper => 2,
-            Shape::Scissors => 3,
-        }
+impl Outcome {
+    pub const ALL: [Outcome; 3] = [Outcome::Lose, Outcome::Draw, Outcome::Win];
+}
+
+/// An odd number of shapes arranged in a circle, where each one beats the (n-1)/2 shapes before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    points: Vec<i64>,
+    outcome_points: [i64; 3],
+}
+
+impl Game {
+    /// Shapes of the classic game, other games number theirs differently
+    pub const ROCK: Shape = Shape(0);
+    pub const PAPER: Shape = Shape(1);
+    pub const SCISSORS: Shape = Shape(2);
+
+    /// A game with a shape for each of `points`, scoring `outcome_points` for a loss, draw and
+    /// win. There must be an odd number of shapes for every pair to have a winner, and at least
+    /// three so every shape can win and lose
+    pub fn new(points: Vec<i64>, outcome_points: [i64; 3]) -> Option<Self> {
+        (points.len() >= 3 && points.len() % 2 == 1).then_some(Game {
+            points,
+            outcome_points,
+        })
     }
 
-    fn play(&self, sh: &Shape) -> i64 {
-        let win = match (self, sh) {
-            (Shape::Rock, Shape::Scissors) => true,
-            (Shape::Scissors, Shape::Paper) => true,
-            (Shape::Paper, Shape::Rock) => true,
-            _ => false,
-        };
-        let outcome_points = if win {
-            6
-        } else if self == sh {
-            3
+    /// Rock, paper, scissors
+    pub fn classic() -> Self {
+        Game::new(vec![1, 2, 3], [0, 3, 6]).unwrap()
+    }
+
+    /// Rock, Spock, paper, lizard, scissors
+    pub fn rpsls() -> Self {
+        Game::new(vec![1, 2, 3, 4, 5], [0, 3, 6]).unwrap()
+    }
+
+    fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn shapes(&self) -> impl Iterator<Item = Shape> {
+        (0..self.len()).map(Shape)
+    }
+
+    pub fn has(&self, shape: Shape) -> bool {
+        shape.0 < self.len()
+    }
+
+    fn beats(&self, own: Shape, theirs: Shape) -> bool {
+        let distance = (own.0 + self.len() - theirs.0) % self.len();
+        (1..=self.len() / 2).contains(&distance)
+    }
+
+    pub fn outcome(&self, own: Shape, theirs: Shape) -> Outcome {
+        if self.beats(own, theirs) {
+            Outcome::Win
+        } else if self.beats(theirs, own) {
+            Outcome::Lose
         } else {
-            0
+            Outcome::Draw
+        }
+    }
+
+    pub fn play(&self, own: Shape, theirs: Shape) -> i64 {
+        self.outcome_points[self.outcome(own, theirs) as usize] + self.points[own.0]
+    }
+
+    /// The shape to throw against `theirs` to get the given outcome
+    pub fn for_outcome(&self, theirs: Shape, outcome: Outcome) -> Shape {
+        self.shapes()
+            .find(|own| self.outcome(*own, theirs) == outcome)
+            .unwrap()
+    }
+}
+
+/// Limits on which sequences of responses are allowed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Constraints {
+    /// Most rounds that may be won
+    pub max_wins: Option<usize>,
+    /// Most times the same shape may be thrown in a row
+    pub max_repeats: Option<usize>,
+}
+
+impl Game {
+    /// The highest scoring shapes to throw against `theirs` within the constraints, along with
+    /// that score, or None if no sequence satisfies them
+    pub fn best_response(
+        &self,
+        theirs: &[Shape],
+        constraints: &Constraints,
+    ) -> Option<(i64, Vec<Shape>)> {
+        // a state is the wins so far, the last shape thrown (`len` before the first round) and how
+        // many times in a row, each collapsing to a single value when it isn't constrained
+        let wins_states = constraints.max_wins.map_or(1, |max| max + 1);
+        let (last_states, run_states) = match constraints.max_repeats {
+            Some(max) => (self.len() + 1, max + 1),
+            None => (1, 1),
         };
-        outcome_points + self.points()
-    }
-
-    fn play_round2(&self, theirs: &Shape) -> i64 {
-        let play = match self {
-            // we match shapes to outcomes for round 2 bc i am shit lol
-            Shape::Rock => {
-                // trying to lose
-                match theirs {
-                    Shape::Rock => Shape::Scissors,
-                    Shape::Paper => Shape::Rock,
-                    Shape::Scissors => Shape::Paper,
-                }
-            }
-            Shape::Paper => *theirs, // trying to draw
-            Shape::Scissors => {
-                match theirs {
-                    // trying to win,
-                    Shape::Rock => Shape::Paper,
-                    Shape::Paper => Shape::Scissors,
-                    Shape::Scissors => Shape::Rock,
+        let index = |wins, last, run| (wins * last_states + last) * run_states + run;
+        let states = wins_states * last_states * run_states;
+
+        // the best score reaching each state, and for every round the state and shape it came from
+        let mut best = vec![None; states];
+        best[index(0, last_states - 1, 0)] = Some(0);
+        let mut parents: Vec<Vec<(u32, u32)>> = vec![];
+        for opponent in theirs {
+            let mut next = vec![None; states];
+            let mut round = vec![(0, 0); states];
+            for (state, score) in best.iter().enumerate() {
+                let Some(score) = score else { continue };
+                let (wins, last, run) = (
+                    state / (last_states * run_states),
+                    state / run_states % last_states,
+                    state % run_states,
+                );
+                for own in self.shapes() {
+                    let won = self.outcome(own, *opponent) == Outcome::Win;
+                    let wins = wins + usize::from(won && constraints.max_wins.is_some());
+                    let (last, run) = match constraints.max_repeats {
+                        Some(_) if last == own.0 => (own.0, run + 1),
+                        Some(_) => (own.0, 1),
+                        None => (0, 0),
+                    };
+                    if wins >= wins_states || run >= run_states {
+                        continue;
+                    }
+                    let key = index(wins, last, run);
+                    let score = score + self.play(own, *opponent);
+                    if next[key].is_none_or(|best| score > best) {
+                        next[key] = Some(score);
+                        round[key] = (state as u32, own.0 as u32);
+                    }
                 }
             }
-        };
+            best = next;
+            parents.push(round);
+        }
 
-        let win = match (play, theirs) {
-            (Shape::Rock, Shape::Scissors) => true,
-            (Shape::Scissors, Shape::Paper) => true,
-            (Shape::Paper, Shape::Rock) => true,
-            _ => false,
-        };
-        let outcome_points = if win {
-            6
-        } else if play == *theirs {
-            3
-        } else {
-            0
-        };
-        outcome_points + play.points()
+        let (mut state, score) = best
+            .into_iter()
+            .enumerate()
+            .filter_map(|(state, score)| Some((state, score?)))
+            .max_by_key(|(_, score)| *score)?;
+        let mut responses = parents
+            .iter()
+            .rev()
+            .map(|round| {
+                let (prev, own) = round[state];
+                state = prev as usize;
+                Shape(own as usize)
+            })
+            .collect_vec();
+        responses.reverse();
+        Some((score, responses))
+    }
+}
+
+/// What the guide's second column tells us to do
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Instruction {
+    Throw(Shape),
+    Aim(Outcome),
+}
+
+impl Instruction {
+    fn against(&self, game: &Game, theirs: Shape) -> Shape {
+        match self {
+            Instruction::Throw(own) => *own,
+            Instruction::Aim(outcome) => game.for_outcome(theirs, *outcome),
+        }
+    }
+}
+
+/// A line of the strategy guide before its letters have been given a meaning
+#[derive(Display, FromStr, Eq, PartialEq, Debug, Copy, Clone)]
+#[display("{theirs} {ours}")]
+pub struct Entry {
+    pub theirs: char,
+    pub ours: char,
+}
+
+#[derive(Display, Debug, Clone, Eq, PartialEq)]
+pub enum GuideError {
+    #[display("`{0}` isn't a shape the opponent can throw")]
+    UnknownTheirs(char),
+    #[display("`{0}` isn't an instruction in this guide")]
+    UnknownOurs(char),
+    #[display("`{0}` stands for a shape that isn't in the game")]
+    OutsideGame(char),
+}
+
+impl Error for GuideError {}
+
+/// How to read the letters in each column of the guide
+#[derive(Debug, Clone)]
+pub struct Scheme {
+    pub game: Game,
+    pub theirs: HashMap<char, Shape>,
+    pub ours: HashMap<char, Instruction>,
+}
+
+impl Scheme {
+    /// Both columns name a classic shape in order, ABC for the opponent and XYZ for us
+    pub fn shapes() -> Self {
+        let game = Game::classic();
+        Scheme {
+            theirs: "ABC".chars().zip(game.shapes()).collect(),
+            ours: "XYZ"
+                .chars()
+                .zip(game.shapes().map(Instruction::Throw))
+                .collect(),
+            game,
+        }
     }
 
-    fn new(chr: &str) -> Self {
-        match chr {
-            "A" | "X" => Shape::Rock,
-            "B" | "Y" => Shape::Paper,
-            "C" | "Z" => Shape::Scissors,
-            _ => panic!("invalid shape {}", chr),
+    /// The second column is the outcome we need, XYZ being lose, draw and win
+    pub fn outcomes() -> Self {
+        Scheme {
+            ours: "XYZ"
+                .chars()
+                .zip(Outcome::ALL.map(Instruction::Aim))
+                .collect(),
+            ..Scheme::shapes()
         }
     }
+
+    /// Decodes an entry into the opponent's shape and ours
+    pub fn decode(&self, entry: &Entry) -> Result<(Shape, Shape), GuideError> {
+        let theirs = *self
+            .theirs
+            .get(&entry.theirs)
+            .ok_or(GuideError::UnknownTheirs(entry.theirs))?;
+        let ours = self
+            .ours
+            .get(&entry.ours)
+            .ok_or(GuideError::UnknownOurs(entry.ours))?;
+        if !self.game.has(theirs) {
+            return Err(GuideError::OutsideGame(entry.theirs));
+        }
+        match ours {
+            Instruction::Throw(own) if !self.game.has(*own) => {
+                Err(GuideError::OutsideGame(entry.ours))
+            }
+            _ => Ok((theirs, ours.against(&self.game, theirs))),
+        }
+    }
+
+    /// Total score from following the whole guide
+    pub fn score(&self, guide: &[Entry]) -> Result<i64, GuideError> {
+        guide.iter().try_fold(0, |total, entry| {
+            let (theirs, own) = self.decode(entry)?;
+            Ok(total + self.game.play(own, theirs))
+        })
+    }
 }
 
 /// https://adventofcode.com/2022/day/2
 #[aoc_generator(day2)]
-pub fn generator(input: &str) -> Vec<(Shape, Shape)> {
+pub fn generator(input: &str) -> Result<Vec<Entry>, parse_display::ParseError> {
     input
         .split("\n")
         .filter(|x| !x.is_empty())
-        .map(|x| {
-            let mut sp = x.split(" ");
-            (
-                Shape::new(sp.next().unwrap()),
-                Shape::new(sp.next().unwrap()),
-            )
-        })
-        .collect()
+        .map(str::parse)
+        .try_collect()
 }
 
 #[aoc(day2, part1)]
-pub fn solve_part1(input: &[(Shape, Shape)]) -> i64 {
-    input.into_iter().map(|(other, own)| own.play(other)).sum()
+pub fn solve_part1(input: &[Entry]) -> Result<i64, GuideError> {
+    Scheme::shapes().score(input)
 }
 
 #[aoc(day2, part2)]
-pub fn solve_part2(input: &[(Shape, Shape)]) -> i64 {
-    input
-        .into_iter()
-        .map(|(theirs, own)| own.play_round2(theirs))
-        .sum()
+pub fn solve_part2(input: &[Entry]) -> Result<i64, GuideError> {
+    Scheme::outcomes().score(input)
 }
 
 #[cfg(test)]
@@ -117,10 +300,10 @@ mod tests {
         let provided = "A Y
 B X
 C Z";
-        assert_eq!(15, solve_part1(&generator(provided)));
+        assert_eq!(Ok(15), solve_part1(&generator(provided).unwrap()));
         assert_eq!(
-            14069,
-            solve_part1(&generator(include_str!("../input/2022/day2.txt")))
+            Ok(14069),
+            solve_part1(&generator(include_str!("../input/2022/day2.txt")).unwrap())
         );
     }
 
@@ -129,10 +312,133 @@ C Z";
         let provided = "A Y
 B X
 C Z";
-        assert_eq!(12, solve_part2(&generator(provided)));
+        assert_eq!(Ok(12), solve_part2(&generator(provided).unwrap()));
         assert_eq!(
-            12411,
-            solve_part2(&generator(include_str!("../input/2022/day2.txt")))
+            Ok(12411),
+            solve_part2(&generator(include_str!("../input/2022/day2.txt")).unwrap())
         );
     }
+
+    #[test]
+    fn custom_schemes() {
+        let guide = generator("R l\nP w\nS d").unwrap();
+        let scheme = Scheme {
+            game: Game::classic(),
+            theirs: "RPS".chars().zip(Game::classic().shapes()).collect(),
+            ours: "lwd"
+                .chars()
+                .zip([Outcome::Lose, Outcome::Win, Outcome::Draw].map(Instruction::Aim))
+                .collect(),
+        };
+        assert_eq!(Ok((Game::ROCK, Game::SCISSORS)), scheme.decode(&guide[0]));
+        assert_eq!(Ok(3 + 9 + 6), scheme.score(&guide));
+        assert_eq!(
+            Err(GuideError::UnknownTheirs('R')),
+            Scheme::shapes().score(&guide)
+        );
+        assert_eq!(
+            Err(GuideError::UnknownOurs('X')),
+            scheme.decode(&"R X".parse().unwrap())
+        );
+
+        let mut outside = Scheme::shapes();
+        outside.theirs.insert('D', Shape(5));
+        outside.ours.insert('W', Instruction::Throw(Shape(3)));
+        assert_eq!(
+            Err(GuideError::OutsideGame('D')),
+            outside.score(&generator("A X\nD Y").unwrap())
+        );
+        assert_eq!(
+            Err(GuideError::OutsideGame('W')),
+            outside.decode(&"A W".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn cyclic_games() {
+        let classic = Game::classic();
+        assert_eq!(Outcome::Win, classic.outcome(Game::PAPER, Game::ROCK));
+        assert_eq!(Outcome::Win, classic.outcome(Game::ROCK, Game::SCISSORS));
+        assert_eq!(Outcome::Lose, classic.outcome(Game::SCISSORS, Game::ROCK));
+        assert_eq!(None, Game::new(vec![1, 2, 3, 4], [0, 3, 6]));
+        assert_eq!(None, Game::new(vec![1], [0, 3, 6]));
+
+        // rock, Spock, paper, lizard, scissors
+        let rpsls = Game::rpsls();
+        let wins = |own| {
+            rpsls
+                .shapes()
+                .filter(|theirs| rpsls.beats(own, *theirs))
+                .collect_vec()
+        };
+        assert_eq!(vec![Shape(3), Shape(4)], wins(Shape(0)));
+        assert_eq!(vec![Shape(0), Shape(4)], wins(Shape(1)));
+        assert_eq!(vec![Shape(1), Shape(2)], wins(Shape(3)));
+        assert_eq!(11, rpsls.play(Shape(4), Shape(3)));
+        assert_eq!(Shape(1), rpsls.for_outcome(Shape(3), Outcome::Lose));
+
+        let big = Game::new((1..=101).collect(), [0, 1, 2]).unwrap();
+        for own in big.shapes() {
+            assert_eq!(
+                50,
+                big.shapes()
+                    .filter(|theirs| big.beats(own, *theirs))
+                    .count()
+            );
+        }
+    }
+
+    #[test]
+    fn best_responses() {
+        let classic = Game::classic();
+        let theirs = [Game::ROCK, Game::PAPER, Game::SCISSORS];
+        let unconstrained = Constraints::default();
+        assert_eq!(
+            Some((24, vec![Game::PAPER, Game::SCISSORS, Game::ROCK])),
+            classic.best_response(&theirs, &unconstrained)
+        );
+        let no_wins = Constraints {
+            max_wins: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            Some((15, theirs.to_vec())),
+            classic.best_response(&theirs, &no_wins)
+        );
+
+        let rocks = [Game::ROCK; 4];
+        let no_triples = Constraints {
+            max_repeats: Some(2),
+            ..Default::default()
+        };
+        let (score, responses) = classic.best_response(&rocks, &no_triples).unwrap();
+        assert_eq!(8 + 8 + 4 + 8, score);
+        assert!(responses
+            .array_windows()
+            .all(|[a, b, c]| !(a == b && b == c)));
+        let one_win = Constraints {
+            max_wins: Some(1),
+            ..no_triples
+        };
+        assert_eq!(
+            8 + 4 + 4 + 4,
+            classic.best_response(&rocks, &one_win).unwrap().0
+        );
+        let never = Constraints {
+            max_repeats: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(None, classic.best_response(&rocks, &never));
+        assert_eq!(Some((0, vec![])), classic.best_response(&[], &never));
+
+        // the guide is good, but not perfect
+        let scheme = Scheme::outcomes();
+        let guide = generator(include_str!("../input/2022/day2.txt")).unwrap();
+        let theirs = guide
+            .iter()
+            .map(|entry| scheme.decode(entry).unwrap().0)
+            .collect_vec();
+        let (best, _) = classic.best_response(&theirs, &unconstrained).unwrap();
+        assert!(best > scheme.score(&guide).unwrap());
+    }
 }
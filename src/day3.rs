@@ -1,74 +1,365 @@
 use crate::prelude::*;
+use std::ops::{BitAnd, BitOr};
+
+/// A set of items, with the bit at each item's priority set
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct ItemSet(u64);
+
+impl ItemSet {
+    fn len(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    fn items(self) -> impl Iterator<Item = char> {
+        ('a'..='z')
+            .chain('A'..='Z')
+            .filter(move |chr| self.0 & 1 << priority(*chr) != 0)
+    }
+
+    /// Priority of the one item in the set
+    fn priority(self) -> i64 {
+        assert_eq!(self.len(), 1);
+        self.0.trailing_zeros() as i64
+    }
+}
+
+impl BitAnd for ItemSet {
+    type Output = ItemSet;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        ItemSet(self.0 & rhs.0)
+    }
+}
+
+impl BitOr for ItemSet {
+    type Output = ItemSet;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        ItemSet(self.0 | rhs.0)
+    }
+}
+
+impl FromIterator<char> for ItemSet {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        ItemSet(iter.into_iter().fold(0, |set, chr| set | 1 << priority(chr)))
+    }
+}
+
+#[derive(Display, Debug, Clone, Eq, PartialEq)]
+pub enum PackingError {
+    #[display("rucksack {line} has {items} items, which don't split into {compartments} compartments")]
+    UnevenCompartments {
+        line: usize,
+        items: usize,
+        compartments: usize,
+    },
+    #[display("{rucksacks} rucksacks don't split into groups of {group_size}")]
+    UnevenGroups { rucksacks: usize, group_size: usize },
+    #[display("rucksacks need at least one compartment")]
+    NoCompartments,
+    #[display("groups need at least one rucksack")]
+    EmptyGroups,
+    #[display("line {line}: `{item}` isn't an item")]
+    InvalidItem { line: usize, item: char },
+    #[display("lines {first}-{last}: group shares \"{shared}\" instead of exactly one badge")]
+    NoUniqueBadge {
+        first: usize,
+        last: usize,
+        shared: String,
+    },
+}
+
+fn check_compartments(compartments: usize) -> Result<(), PackingError> {
+    match compartments {
+        0 => Err(PackingError::NoCompartments),
+        _ => Ok(()),
+    }
+}
+
+fn check_group_size(group_size: usize) -> Result<(), PackingError> {
+    match group_size {
+        0 => Err(PackingError::EmptyGroups),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the rucksacks split evenly into groups
+fn check_groups(rucksacks: &[Rucksack], group_size: usize) -> Result<(), PackingError> {
+    check_group_size(group_size)?;
+    if !rucksacks.len().is_multiple_of(group_size) {
+        return Err(PackingError::UnevenGroups {
+            rucksacks: rucksacks.len(),
+            group_size,
+        });
+    }
+    Ok(())
+}
 
 #[derive(Debug)]
 pub struct Rucksack {
-    left: HashSet<char>,
-    right: HashSet<char>,
+    compartments: Vec<ItemSet>,
 }
 
 impl Rucksack {
-    fn find_repeated(&self) -> char {
-        let repeated = self.left.intersection(&self.right).collect::<Vec<_>>();
-        assert_eq!(repeated.len(), 1);
-        *repeated[0]
+    fn find_repeated(&self) -> ItemSet {
+        self.compartments
+            .iter()
+            .copied()
+            .reduce(|accum, next| accum & next)
+            .unwrap_or_default()
     }
 
-    fn union(&self) -> HashSet<char> {
-        self.left.union(&self.right).copied().collect()
+    fn union(&self) -> ItemSet {
+        self.compartments
+            .iter()
+            .fold(ItemSet::default(), |accum, next| accum | *next)
     }
 }
 
-fn priority(chr: char) -> i64 {
+fn try_priority(chr: char) -> Option<i64> {
     match chr {
-        'a'..='z' => chr as i64 - 0x60,
-        'A'..='Z' => chr as i64 - 0x40 + 26,
-        _ => panic!("invalid char"),
+        'a'..='z' => Some(chr as i64 - 0x60),
+        'A'..='Z' => Some(chr as i64 - 0x40 + 26),
+        _ => None,
     }
 }
 
-fn intersection<const N: usize>(rucksacks: &[Rucksack; N]) -> char {
-    assert!(rucksacks.len() > 0);
-    let shared = rucksacks
-        .into_iter()
+fn priority(chr: char) -> i64 {
+    try_priority(chr).expect("invalid char")
+}
+
+fn intersection(rucksacks: &[Rucksack]) -> ItemSet {
+    assert!(!rucksacks.is_empty());
+    rucksacks
+        .iter()
         .map(Rucksack::union)
-        .reduce(|accum, next| &accum & &next)
-        .unwrap();
-    assert_eq!(shared.len(), 1);
-    *shared.iter().next().unwrap()
+        .reduce(|accum, next| accum & next)
+        .unwrap()
 }
 
-/// https://adventofcode.com/2022/day/3
-#[aoc_generator(day3)]
-pub fn generator(input: &str) -> Vec<Rucksack> {
+/// Parses one rucksack per line, splitting each into equally sized compartments
+pub fn parse(input: &str, compartments: usize) -> Result<Vec<Rucksack>, PackingError> {
+    check_compartments(compartments)?;
     input
         .lines()
-        .map(|rucksack| {
-            assert_eq!(rucksack.len() % 2, 0);
-            let (left, right) = rucksack.split_at(rucksack.len() / 2);
-            Rucksack {
-                left: left.chars().collect(),
-                right: right.chars().collect(),
+        .enumerate()
+        .map(|(idx, rucksack)| {
+            let items = rucksack.chars().collect_vec();
+            if let Some(item) = items.iter().find(|item| try_priority(**item).is_none()) {
+                return Err(PackingError::InvalidItem {
+                    line: idx + 1,
+                    item: *item,
+                });
             }
+            if !items.len().is_multiple_of(compartments) {
+                return Err(PackingError::UnevenCompartments {
+                    line: idx + 1,
+                    items: items.len(),
+                    compartments,
+                });
+            }
+            // chunks(0) panics, and an empty line has no compartments to fill anyway
+            let size = (items.len() / compartments).max(1);
+            Ok(Rucksack {
+                compartments: items
+                    .chunks(size)
+                    .map(|items| items.iter().copied().collect())
+                    .collect(),
+            })
         })
         .collect()
 }
 
+/// Sums the priorities of the item shared by every rucksack in each group
+pub fn badge_priorities(rucksacks: &[Rucksack], group_size: usize) -> Result<i64, PackingError> {
+    check_groups(rucksacks, group_size)?;
+    rucksacks
+        .chunks(group_size)
+        .map(intersection)
+        .enumerate()
+        .map(|(n, badge)| match badge.len() {
+            1 => Ok(badge.priority()),
+            _ => Err(PackingError::NoUniqueBadge {
+                first: n * group_size + 1,
+                last: (n + 1) * group_size,
+                shared: badge.items().collect(),
+            }),
+        })
+        .sum()
+}
+
+#[derive(Display, Debug, Clone, Eq, PartialEq)]
+pub enum Problem {
+    #[display("{0}")]
+    Packing(PackingError),
+    #[display("line {line}: compartments share \"{shared}\" instead of exactly one item")]
+    SharedItems { line: usize, shared: String },
+}
+
+/// Reports every problem in the input rather than stopping at the first one. Invalid characters
+/// are reported and then left out of the compartment and badge checks
+pub fn check(input: &str, compartments: usize, group_size: usize) -> Vec<Problem> {
+    // nothing else can be checked without both sizes
+    let config = [
+        check_compartments(compartments),
+        check_group_size(group_size),
+    ]
+    .into_iter()
+    .filter_map(Result::err)
+    .map(Problem::Packing)
+    .collect_vec();
+    if !config.is_empty() {
+        return config;
+    }
+    let is_item = |chr: &char| try_priority(*chr).is_some();
+    let mut problems = vec![];
+    let mut contents = vec![];
+    for (idx, rucksack) in input.lines().enumerate() {
+        let line = idx + 1;
+        let items = rucksack.chars().collect_vec();
+        problems.extend(
+            items
+                .iter()
+                .filter(|item| !is_item(item))
+                .map(|item| Problem::Packing(PackingError::InvalidItem { line, item: *item })),
+        );
+        if items.len().is_multiple_of(compartments) {
+            let rucksack = Rucksack {
+                compartments: items
+                    .chunks((items.len() / compartments).max(1))
+                    .map(|items| items.iter().copied().filter(is_item).collect())
+                    .collect(),
+            };
+            let shared = rucksack.find_repeated();
+            if shared.len() != 1 {
+                problems.push(Problem::SharedItems {
+                    line,
+                    shared: shared.items().collect(),
+                });
+            }
+        } else {
+            problems.push(Problem::Packing(PackingError::UnevenCompartments {
+                line,
+                items: items.len(),
+                compartments,
+            }));
+        }
+        contents.push(items.into_iter().filter(is_item).collect::<ItemSet>());
+    }
+
+    if !contents.len().is_multiple_of(group_size) {
+        problems.push(Problem::Packing(PackingError::UnevenGroups {
+            rucksacks: contents.len(),
+            group_size,
+        }));
+    }
+    for (n, group) in contents.chunks_exact(group_size).enumerate() {
+        let badge = group.iter().fold(ItemSet(u64::MAX), |accum, next| accum & *next);
+        if badge.len() != 1 {
+            problems.push(Problem::Packing(PackingError::NoUniqueBadge {
+                first: n * group_size + 1,
+                last: (n + 1) * group_size,
+                shared: badge.items().collect(),
+            }));
+        }
+    }
+    problems
+}
+
+/// Backtracking search for groups that each share exactly one item
+struct GroupSearch<'a> {
+    contents: &'a [ItemSet],
+    group_size: usize,
+    assigned: Vec<bool>,
+    groups: Vec<Vec<usize>>,
+    // assignments we've already seen fail, whatever order we reached them in
+    dead_ends: HashSet<Vec<bool>>,
+}
+
+impl GroupSearch<'_> {
+    /// Groups up the earliest unassigned rucksack, then the rest
+    fn assign(&mut self) -> bool {
+        let Some(first) = self.assigned.iter().position(|assigned| !assigned) else {
+            return true;
+        };
+        if self.dead_ends.contains(&self.assigned) {
+            return false;
+        }
+        self.assigned[first] = true;
+        if self.extend(&mut vec![first], first + 1, self.contents[first]) {
+            return true;
+        }
+        self.assigned[first] = false;
+        self.dead_ends.insert(self.assigned.clone());
+        false
+    }
+
+    /// Fills out `group` from rucksacks at or after `from`, where `shared` is what it has in common so far
+    fn extend(&mut self, group: &mut Vec<usize>, from: usize, shared: ItemSet) -> bool {
+        if group.len() == self.group_size {
+            if shared.len() != 1 {
+                return false;
+            }
+            self.groups.push(group.clone());
+            if self.assign() {
+                return true;
+            }
+            self.groups.pop();
+            return false;
+        }
+        for next in from..self.contents.len() {
+            let shared = shared & self.contents[next];
+            if self.assigned[next] || shared.len() == 0 {
+                continue;
+            }
+            self.assigned[next] = true;
+            group.push(next);
+            if self.extend(group, next + 1, shared) {
+                return true;
+            }
+            group.pop();
+            self.assigned[next] = false;
+        }
+        false
+    }
+}
+
+/// Partitions rucksacks in any order into groups that each share exactly one badge, returning the
+/// indices in each group. `None` means the search was exhaustive and no such partition exists
+pub fn find_groups(
+    rucksacks: &[Rucksack],
+    group_size: usize,
+) -> Result<Option<Vec<Vec<usize>>>, PackingError> {
+    check_groups(rucksacks, group_size)?;
+    let contents = rucksacks.iter().map(Rucksack::union).collect_vec();
+    let mut search = GroupSearch {
+        contents: &contents,
+        group_size,
+        assigned: vec![false; contents.len()],
+        groups: vec![],
+        dead_ends: HashSet::new(),
+    };
+    Ok(search.assign().then_some(search.groups))
+}
+
+/// https://adventofcode.com/2022/day/3
+#[aoc_generator(day3)]
+pub fn generator(input: &str) -> Vec<Rucksack> {
+    parse(input, 2).unwrap()
+}
+
 #[aoc(day3, part1)]
 pub fn solve_part1(input: &[Rucksack]) -> i64 {
     input
         .into_iter()
         .map(Rucksack::find_repeated)
-        .map(priority)
+        .map(ItemSet::priority)
         .sum()
 }
 
 #[aoc(day3, part2)]
 pub fn solve_part2(input: &[Rucksack]) -> i64 {
-    input
-        .array_chunks::<3>()
-        .map(intersection)
-        .map(priority)
-        .sum()
+    badge_priorities(input, 3).unwrap()
 }
 
 #[cfg(test)]
@@ -104,4 +395,143 @@ CrZsJsPPZsGzwwsLwLmpwMDw";
             solve_part2(&generator(include_str!("../input/2022/day3.txt")))
         );
     }
+
+    #[test]
+    fn item_sets() {
+        let lhs: ItemSet = "abcZ".chars().collect();
+        let rhs: ItemSet = "cdZ".chars().collect();
+        assert_eq!(5, (lhs | rhs).len());
+        assert_eq!("cZ".chars().collect::<ItemSet>(), lhs & rhs);
+        assert_eq!(52, (lhs & "Z".chars().collect()).priority());
+        assert_eq!(0, ItemSet::default().len());
+    }
+
+    #[test]
+    fn configurable_groups() {
+        let rucksacks = parse(EXAMPLE_INPUT, 2).unwrap();
+        assert_eq!(
+            Err(PackingError::UnevenGroups {
+                rucksacks: 6,
+                group_size: 4
+            }),
+            badge_priorities(&rucksacks, 4)
+        );
+        assert_eq!(
+            Err(PackingError::UnevenCompartments {
+                line: 1,
+                items: 24,
+                compartments: 5
+            }),
+            parse(EXAMPLE_INPUT, 5).map(|_| ())
+        );
+        assert!(parse("abcxcyzcq\nab", 3).is_err());
+        let thirds = parse("abcxcyzcq", 3).unwrap();
+        assert_eq!(priority('c'), solve_part1(&thirds));
+        assert_eq!(
+            Err(PackingError::NoCompartments),
+            parse(EXAMPLE_INPUT, 0).map(|_| ())
+        );
+        assert_eq!(
+            Err(PackingError::EmptyGroups),
+            badge_priorities(&rucksacks, 0)
+        );
+        assert_eq!(
+            Err(PackingError::InvalidItem { line: 2, item: '1' }),
+            parse("ab\na1", 2).map(|_| ())
+        );
+        assert_eq!(
+            Err(PackingError::NoUniqueBadge {
+                first: 1,
+                last: 3,
+                shared: "ab".to_string()
+            }),
+            badge_priorities(&parse("ab\nab\nab", 2).unwrap(), 3)
+        );
+    }
+
+    #[test]
+    fn reports_problems() {
+        assert_eq!(Vec::<Problem>::new(), check(EXAMPLE_INPUT, 2, 3));
+        assert_eq!(
+            vec![
+                Problem::Packing(PackingError::NoCompartments),
+                Problem::Packing(PackingError::EmptyGroups),
+            ],
+            check(EXAMPLE_INPUT, 0, 0)
+        );
+        assert_eq!(
+            vec![Problem::Packing(PackingError::EmptyGroups)],
+            check(EXAMPLE_INPUT, 2, 0)
+        );
+
+        let broken = "abcdef\nab1b\nabab\nxyz";
+        assert_eq!(
+            vec![
+                Problem::SharedItems {
+                    line: 1,
+                    shared: "".to_string()
+                },
+                Problem::Packing(PackingError::InvalidItem { line: 2, item: '1' }),
+                Problem::SharedItems {
+                    line: 3,
+                    shared: "ab".to_string()
+                },
+                Problem::Packing(PackingError::UnevenCompartments {
+                    line: 4,
+                    items: 3,
+                    compartments: 2
+                }),
+                Problem::Packing(PackingError::UnevenGroups {
+                    rucksacks: 4,
+                    group_size: 3
+                }),
+                Problem::Packing(PackingError::NoUniqueBadge {
+                    first: 1,
+                    last: 3,
+                    shared: "ab".to_string()
+                }),
+            ],
+            check(broken, 2, 3)
+        );
+        assert_eq!(
+            "line 2: `1` isn't an item",
+            Problem::Packing(PackingError::InvalidItem { line: 2, item: '1' }).to_string()
+        );
+    }
+
+    #[test]
+    fn discovers_groups() {
+        let shuffled = "wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
+vJrwpWtwJgWrhcsFMMfFFhFp
+CrZsJsPPZsGzwwsLwLmpwMDw
+jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL
+ttgJtRGJQctTZtZT
+PmmdzqPrVvPwwTWBwg";
+        let rucksacks = generator(shuffled);
+        let groups = find_groups(&rucksacks, 3).unwrap().unwrap();
+        assert_eq!(
+            (0..6).collect_vec(),
+            groups.iter().flatten().copied().sorted().collect_vec()
+        );
+        for group in &groups {
+            let badge = group
+                .iter()
+                .map(|idx| rucksacks[*idx].union())
+                .reduce(|accum, next| accum & next)
+                .unwrap();
+            assert_eq!(1, badge.len());
+        }
+
+        // the only groups with anything in common share two items
+        assert_eq!(None, find_groups(&generator("ab\ncd\nab\ncd"), 2).unwrap());
+        assert!(find_groups(&rucksacks, 4).is_err());
+        assert_eq!(Err(PackingError::EmptyGroups), find_groups(&rucksacks, 0));
+    }
+
+    const EXAMPLE_INPUT: &str = "vJrwpWtwJgWrhcsFMMfFFhFp
+jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL
+PmmdzqPrVvPwwTWBwg
+wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
+ttgJtRGJQctTZtZT
+CrZsJsPPZsGzwwsLwLmpwMDw";
 }
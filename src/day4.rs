@@ -1,62 +1,338 @@
 use crate::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Display, FromStr, PartialEq, Debug, Clone)]
-#[display("{lo}-{hi}")]
+// open ended ranges like `5-` run all the way up to here
+const OPEN_END: i64 = i64::MAX;
+
+#[derive(PartialEq, Debug, Clone)]
 pub struct SectionRange {
     lo: i64,
     hi: i64,
 }
 
+#[derive(Display, Debug, Clone, Eq, PartialEq)]
+pub enum RangeError {
+    #[display("`{0}` isn't a section range")]
+    Invalid(String),
+    #[display("`{0}` ends before it starts")]
+    Inverted(String),
+}
+
+impl Error for RangeError {}
+
+#[derive(Display, Debug, Clone, Eq, PartialEq)]
+#[display("line {line}: {error}")]
+pub struct LineError {
+    pub line: usize,
+    pub error: RangeError,
+}
+
+impl Error for LineError {}
+
+impl fmt::Display for SectionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.hi {
+            OPEN_END => write!(f, "{}-", self.lo),
+            hi => write!(f, "{}-{}", self.lo, hi),
+        }
+    }
+}
+
+/// Accepts `lo-hi`, `lo-` for everything from `lo` up and `n` for a single section
+impl FromStr for SectionRange {
+    type Err = RangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let section = |n: &str| {
+            n.parse::<i64>()
+                .ok()
+                .filter(|n| *n >= 0)
+                .ok_or_else(|| RangeError::Invalid(s.to_string()))
+        };
+        let (lo, hi) = match s.split_once('-') {
+            None => section(s).map(|n| (n, n))?,
+            Some((lo, "")) => (section(lo)?, OPEN_END),
+            Some((lo, hi)) => (section(lo)?, section(hi)?),
+        };
+        if lo > hi {
+            return Err(RangeError::Inverted(s.to_string()));
+        }
+        Ok(SectionRange { lo, hi })
+    }
+}
+
 impl SectionRange {
     fn is_fully_within(&self, other: &SectionRange) -> bool {
         other.lo >= self.lo && other.hi <= self.hi
     }
 
-    fn is_partially_within(&self, other: &SectionRange) -> bool {
-        other.lo >= self.lo && other.lo <= self.hi || other.hi >= self.lo && other.hi <= self.hi
+    fn contains(&self, section: i64) -> bool {
+        self.lo <= section && section <= self.hi
+    }
+
+    fn len(&self) -> i64 {
+        (self.hi - self.lo).saturating_add(1)
+    }
+}
+
+/// Every section covered by a collection of ranges. The ranges are merged into sorted, disjoint
+/// runs but kept around as well so we can still tell which elf covers what
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntervalSet {
+    members: Vec<SectionRange>,
+    merged: Vec<SectionRange>,
+}
+
+impl IntervalSet {
+    pub fn insert(&mut self, range: SectionRange) {
+        // merged runs are sorted and never touch, so the ones joining the new range sit together
+        let start = self
+            .merged
+            .partition_point(|run| run.hi.saturating_add(1) < range.lo);
+        let end = self
+            .merged
+            .partition_point(|run| run.lo <= range.hi.saturating_add(1));
+        let mut joined = range.clone();
+        if start < end {
+            joined.lo = joined.lo.min(self.merged[start].lo);
+            joined.hi = joined.hi.max(self.merged[end - 1].hi);
+        }
+        self.merged.splice(start..end, [joined]);
+        self.members.push(range);
+    }
+
+    fn merge(&mut self) {
+        let mut sorted = self.members.clone();
+        sorted.sort_by_key(|range| range.lo);
+        self.merged = sorted.into_iter().fold(vec![], |mut merged, next| {
+            match merged.last_mut() {
+                // sections are whole numbers so touching ranges merge too
+                Some(last) if next.lo <= last.hi.saturating_add(1) => {
+                    last.hi = last.hi.max(next.hi)
+                }
+                _ => merged.push(next),
+            }
+            merged
+        });
+    }
+
+    pub fn ranges(&self) -> &[SectionRange] {
+        &self.merged
+    }
+
+    pub fn contains(&self, section: i64) -> bool {
+        self.merged.iter().any(|range| range.contains(section))
+    }
+
+    /// Total number of sections covered
+    pub fn covered(&self) -> i64 {
+        self.merged
+            .iter()
+            .map(SectionRange::len)
+            .fold(0, i64::saturating_add)
+    }
+
+    /// The uncovered sections between the lowest and highest covered ones
+    pub fn gaps(&self) -> Vec<SectionRange> {
+        self.merged
+            .iter()
+            .tuple_windows()
+            .map(|(lhs, rhs)| SectionRange {
+                lo: lhs.hi + 1,
+                hi: rhs.lo - 1,
+            })
+            .collect()
+    }
+
+    /// Indices, in insertion order, of every range covering `section`
+    pub fn members_at(&self, section: i64) -> Vec<usize> {
+        self.members
+            .iter()
+            .positions(|range| range.contains(section))
+            .collect()
+    }
+}
+
+impl FromIterator<SectionRange> for IntervalSet {
+    fn from_iter<I: IntoIterator<Item = SectionRange>>(iter: I) -> Self {
+        let mut set = IntervalSet {
+            members: iter.into_iter().collect(),
+            merged: vec![],
+        };
+        set.merge();
+        set
+    }
+}
+
+impl fmt::Display for IntervalSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.merged.iter().join(","))
+    }
+}
+
+impl FromStr for IntervalSet {
+    type Err = RangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',').map(str::parse).collect()
     }
 }
 
-#[derive(Display, FromStr, PartialEq, Debug, Clone)]
-#[display("{lhs},{rhs}")]
-pub struct Pair {
-    pub lhs: SectionRange,
-    pub rhs: SectionRange,
+/// Any number of elves assigned to work together, written as comma separated ranges
+#[derive(PartialEq, Debug, Clone)]
+pub struct Group {
+    pub members: Vec<SectionRange>,
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.members.iter().join(","))
+    }
 }
 
-impl Pair {
-    fn is_fully_contained(&self) -> bool {
-        self.lhs.is_fully_within(&self.rhs) || self.rhs.is_fully_within(&self.lhs)
+impl FromStr for Group {
+    type Err = RangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Group {
+            members: s.split(',').map(str::parse).try_collect()?,
+        })
+    }
+}
+
+impl Group {
+    /// Whether any member's range is contained in another's
+    fn has_nested(&self) -> bool {
+        self.members
+            .iter()
+            .tuple_combinations()
+            .any(|(lhs, rhs)| lhs.is_fully_within(rhs) || rhs.is_fully_within(lhs))
+    }
+
+    /// Whether there's a section every member is assigned to
+    fn all_overlap(&self) -> bool {
+        let lo = self.members.iter().map(|range| range.lo).max();
+        let hi = self.members.iter().map(|range| range.hi).min();
+        lo <= hi
     }
 
-    fn is_partially_contained(&self) -> bool {
-        self.lhs.is_partially_within(&self.rhs) || self.rhs.is_partially_within(&self.lhs)
+    /// Members whose whole range is already covered by one other member. Of several identical
+    /// ranges only the first is kept
+    pub fn redundant(&self) -> Vec<usize> {
+        self.members
+            .iter()
+            .enumerate()
+            .filter(|(idx, range)| {
+                self.members.iter().enumerate().any(|(other_idx, other)| {
+                    other_idx != *idx
+                        && other.is_fully_within(range)
+                        && (*other != **range || other_idx < *idx)
+                })
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+}
+
+/// Every elf's range across the whole input in order, along with the group it belongs to
+fn elves(groups: &[Group]) -> Vec<(usize, SectionRange)> {
+    groups
+        .iter()
+        .enumerate()
+        .flat_map(|(group, members)| {
+            members
+                .members
+                .iter()
+                .map(move |range| (group, range.clone()))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coverage {
+    /// The lowest section with the most elves assigned to it, and how many elves that is
+    pub busiest: Option<(i64, usize)>,
+    /// Elves from different groups whose ranges overlap, numbered in the order they appear
+    pub overlaps: Vec<(usize, usize)>,
+    /// Sections between the lowest and highest assigned ones that no elf covers
+    pub unassigned: Vec<SectionRange>,
+}
+
+/// Sweeps across every range at once in O((n + k) log(n + k)) for n elves and k overlaps found
+pub fn analyse(groups: &[Group]) -> Coverage {
+    let elves = elves(groups);
+    let order = (0..elves.len()).sorted_by_key(|elf| elves[*elf].1.lo);
+    // active elves by group, so a group's own members are skipped without walking them
+    let mut active: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+    let mut expiring: BinaryHeap<Reverse<(i64, usize)>> = BinaryHeap::new();
+    let mut busiest = None;
+    let mut overlaps = vec![];
+    for elf in order {
+        let (group, range) = &elves[elf];
+        // everything still active after this overlaps the new range at its first section
+        while let Some(&Reverse((hi, other))) = expiring.peek() {
+            if hi >= range.lo {
+                break;
+            }
+            expiring.pop();
+            let members = active.get_mut(&elves[other].0).unwrap();
+            members.remove(&other);
+            if members.is_empty() {
+                active.remove(&elves[other].0);
+            }
+        }
+        overlaps.extend(
+            active
+                .iter()
+                .filter(|(other_group, _)| *other_group != group)
+                .flat_map(|(_, members)| members)
+                .map(|other| (elf.min(*other), elf.max(*other))),
+        );
+        active.entry(*group).or_default().insert(elf);
+        expiring.push(Reverse((range.hi, elf)));
+        if busiest.is_none_or(|(_, count)| expiring.len() > count) {
+            busiest = Some((range.lo, expiring.len()));
+        }
+    }
+    overlaps.sort();
+    Coverage {
+        busiest,
+        overlaps,
+        unassigned: elves
+            .into_iter()
+            .map(|(_, range)| range)
+            .collect::<IntervalSet>()
+            .gaps(),
     }
 }
 
 /// https://adventofcode.com/2022/day/4
 #[aoc_generator(day4)]
-pub fn generator(input: &str) -> Vec<Pair> {
-    input.lines().map(|x| x.parse().unwrap()).collect()
+pub fn generator(input: &str) -> Result<Vec<Group>, LineError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(idx, x)| {
+            x.parse().map_err(|error| LineError {
+                line: idx + 1,
+                error,
+            })
+        })
+        .collect()
 }
 
 #[aoc(day4, part1)]
-pub fn solve_part1(input: &[Pair]) -> usize {
-    input
-        .into_iter()
-        .cloned()
-        .filter(Pair::is_fully_contained)
-        .count()
+pub fn solve_part1(input: &[Group]) -> usize {
+    input.iter().filter(|group| group.has_nested()).count()
 }
 
 #[aoc(day4, part2)]
-pub fn solve_part2(input: &[Pair]) -> usize {
-    input
-        .into_iter()
-        .cloned()
-        .filter(Pair::is_partially_contained)
-        .count()
-
+pub fn solve_part2(input: &[Group]) -> usize {
+    input.iter().filter(|group| group.all_overlap()).count()
 }
 
 #[cfg(test)]
@@ -71,10 +347,10 @@ mod tests {
 2-8,3-7
 6-6,4-6
 2-6,4-8";
-        assert_eq!(2, solve_part1(&generator(provided)));
+        assert_eq!(2, solve_part1(&generator(provided).unwrap()));
         assert_eq!(
             475,
-            solve_part1(&generator(include_str!("../input/2022/day4.txt")))
+            solve_part1(&generator(include_str!("../input/2022/day4.txt")).unwrap())
         );
     }
 
@@ -86,10 +362,136 @@ mod tests {
 2-8,3-7
 6-6,4-6
 2-6,4-8";
-        assert_eq!(4, solve_part2(&generator(provided)));
+        assert_eq!(4, solve_part2(&generator(provided).unwrap()));
         assert_eq!(
             825,
-            solve_part2(&generator(include_str!("../input/2022/day4.txt")))
+            solve_part2(&generator(include_str!("../input/2022/day4.txt")).unwrap())
         );
     }
+
+    #[test]
+    fn interval_sets() {
+        let set = "2-4,6-8,3-5,10-12".parse::<IntervalSet>().unwrap();
+        assert_eq!("2-8,10-12", set.to_string());
+        assert_eq!(10, set.covered());
+        assert_eq!(vec![SectionRange { lo: 9, hi: 9 }], set.gaps());
+        assert_eq!(vec![0, 2], set.members_at(4));
+        assert!(set.contains(11));
+        assert!(!set.contains(9));
+
+        let groups = generator("2-4,6-8\n2-3,4-5").unwrap();
+        let set = groups
+            .iter()
+            .flat_map(|group| group.members.clone())
+            .collect::<IntervalSet>();
+        assert_eq!("2-8", set.to_string());
+        assert!(set.gaps().is_empty());
+    }
+
+    #[test]
+    fn inserts_match_bulk_merge() {
+        let mut lcg = Lcg::new();
+        let ranges = (0..500)
+            .map(|_| {
+                let lo = lcg.below(1_000) as i64;
+                SectionRange {
+                    lo,
+                    hi: lo + lcg.below(10) as i64,
+                }
+            })
+            .collect_vec();
+        let mut set = IntervalSet::default();
+        for (idx, range) in ranges.iter().enumerate() {
+            set.insert(range.clone());
+            let bulk = ranges[..=idx].iter().cloned().collect::<IntervalSet>();
+            assert_eq!(bulk, set);
+        }
+        set.insert(SectionRange {
+            lo: 0,
+            hi: OPEN_END,
+        });
+        assert_eq!("0-", set.to_string());
+    }
+
+    #[test]
+    fn sweeps_all_ranges() {
+        let groups = generator(EXAMPLE_INPUT).unwrap();
+        let coverage = analyse(&groups);
+        assert_eq!(Some((6, 8)), coverage.busiest);
+        assert!(coverage.unassigned.is_empty());
+
+        let elves = elves(&groups);
+        let brute_force = (0..elves.len())
+            .tuple_combinations()
+            .filter(|(a, b)| elves[*a].0 != elves[*b].0)
+            .filter(|(a, b)| elves[*a].1.lo <= elves[*b].1.hi && elves[*b].1.lo <= elves[*a].1.hi)
+            .collect_vec();
+        assert_eq!(brute_force, coverage.overlaps);
+
+        let coverage = analyse(&generator("1-2,8-9\n4-4,5-5").unwrap());
+        assert_eq!(Some((1, 1)), coverage.busiest);
+        assert_eq!(Vec::<(usize, usize)>::new(), coverage.overlaps);
+        assert_eq!(
+            vec![SectionRange { lo: 3, hi: 3 }, SectionRange { lo: 6, hi: 7 }],
+            coverage.unassigned
+        );
+    }
+
+    #[test]
+    fn validates_ranges() {
+        let groups = generator("7,5-\n2-4,3").unwrap();
+        assert_eq!("7-7,5-", groups[0].to_string());
+        assert!(groups[0].has_nested());
+        assert!(groups[1].has_nested());
+        assert_eq!(
+            "2-",
+            "2-4,3-,6-8".parse::<IntervalSet>().unwrap().to_string()
+        );
+
+        assert_eq!(
+            Err(LineError {
+                line: 2,
+                error: RangeError::Inverted("5-3".to_string())
+            }),
+            generator("1-2,3-4\n5-3,1-1")
+        );
+        assert_eq!(
+            "line 1: `-3-4` isn't a section range",
+            generator("-3-4,5-6").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn sweeps_large_groups() {
+        // every member overlaps every other, but only across groups
+        let line = (1..=200).map(|n| format!("{n}-1000")).join(",");
+        let groups = generator(&format!("{line}\n{line}")).unwrap();
+        let coverage = analyse(&groups);
+        assert_eq!(200 * 200, coverage.overlaps.len());
+        assert!(coverage.overlaps.iter().all(|(a, b)| a / 200 != b / 200));
+        assert_eq!(Some((200, 400)), coverage.busiest);
+    }
+
+    #[test]
+    fn n_way_groups() {
+        let groups = generator("1-3,5-7,2-6\n1-4,3-6,4-9,4\n1-2,3-4,2-3").unwrap();
+        assert_eq!(1, solve_part1(&groups));
+        assert_eq!(1, solve_part2(&groups));
+        assert!(!groups[0].has_nested());
+        assert!(!groups[0].all_overlap());
+        assert!(groups[1].all_overlap());
+        assert_eq!(vec![3], groups[1].redundant());
+        assert_eq!(
+            vec![1, 2],
+            "1-9,2-3,1-9".parse::<Group>().unwrap().redundant()
+        );
+        assert_eq!("1-2,3-4,2-3", groups[2].to_string());
+    }
+
+    const EXAMPLE_INPUT: &str = "2-4,6-8
+2-3,4-5
+5-7,7-9
+2-8,3-7
+6-6,4-6
+2-6,4-8";
 }
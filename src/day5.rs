@@ -1,96 +1,287 @@
 use crate::prelude::*;
+use std::collections::HashMap;
+use std::fmt::{self, Formatter};
+use std::io::Write;
 use std::str::FromStr;
 
-#[derive(Display, FromStr, PartialEq, Debug)]
+#[derive(Display, FromStr, PartialEq, Debug, Copy, Clone)]
 pub enum Instruction {
     #[display("move {0} from {1} to {2}")]
     Move(usize, usize, usize),
 }
 
-#[derive(Debug, Clone)]
-pub struct Stacks {
-    rows: Vec<Vec<u8>>,
+/// Stacks of crates, bottom first. Crates are labels unless we're tracking their identities
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Stacks<T = String> {
+    rows: Vec<Vec<T>>,
+}
+
+#[derive(Display, Debug, Clone, Eq, PartialEq)]
+pub enum MoveError {
+    #[display("instruction {index}: there is no stack {stack}")]
+    NoSuchStack { index: usize, stack: usize },
+    #[display("instruction {index}: stack {stack} is {shortfall} crates short")]
+    NotEnoughCrates {
+        index: usize,
+        stack: usize,
+        shortfall: usize,
+    },
+}
+
+impl<T> Stacks<T> {
+    /// Checks that `instr` (the `index`th instruction) can be applied to these stacks
+    pub fn check(&self, index: usize, instr: &Instruction) -> Result<(), MoveError> {
+        let heights = self.rows.iter().map(Vec::len).collect_vec();
+        check_heights(&heights, index, instr)
+    }
+
+    /// Checks every instruction in order without moving anything
+    pub fn dry_run(&self, instrs: &[Instruction]) -> Result<(), MoveError> {
+        // every crane moves the same number of crates so only the heights matter
+        let mut heights = self.rows.iter().map(Vec::len).collect_vec();
+        for (index, instr) in instrs.iter().enumerate() {
+            check_heights(&heights, index, instr)?;
+            let Instruction::Move(amount, src, dest) = instr;
+            heights[src - 1] -= amount;
+            heights[dest - 1] += amount;
+        }
+        Ok(())
+    }
+
+    /// Every instruction that can legally be applied to these stacks
+    fn moves(&self) -> Vec<Instruction> {
+        let mut moves = vec![];
+        for (src, row) in self.rows.iter().enumerate() {
+            for dest in (0..self.rows.len()).filter(|dest| *dest != src) {
+                for amount in 1..=row.len() {
+                    moves.push(Instruction::Move(amount, src + 1, dest + 1));
+                }
+            }
+        }
+        moves
+    }
+
+    /// Borrows both rows touched by a move along with where the moved crates start in the source.
+    /// Panics on the same moves `check` rejects. Crates moved onto their own stack end up where they
+    /// started, so there's nothing to borrow and this returns None
+    fn lift(
+        &mut self,
+        amount: usize,
+        src: usize,
+        dest: usize,
+    ) -> Option<([&mut Vec<T>; 2], usize)> {
+        let stacks = 1..=self.rows.len();
+        assert!(
+            stacks.contains(&src) && stacks.contains(&dest),
+            "no such stack"
+        );
+        let start = self.rows[src - 1]
+            .len()
+            .checked_sub(amount)
+            .expect("trying to move more elements than exist");
+        let rows = self.rows.get_disjoint_mut([src - 1, dest - 1]).ok()?;
+        Some((rows, start))
+    }
 }
 
 impl Stacks {
-    fn apply_part_one(mut self, instr: &Instruction) -> Self {
+    fn get_top(&self) -> String {
+        self
+            .rows
+            .iter()
+            .filter_map(|x| x.last())
+            .map(String::as_str)
+            .collect()
+    }
+    
+}
+
+fn check_heights(heights: &[usize], index: usize, instr: &Instruction) -> Result<(), MoveError> {
+    let Instruction::Move(amount, src, dest) = *instr;
+    for stack in [src, dest] {
+        if stack == 0 || stack > heights.len() {
+            return Err(MoveError::NoSuchStack { index, stack });
+        }
+    }
+    if heights[src - 1] < amount {
+        return Err(MoveError::NotEnoughCrates {
+            index,
+            stack: src,
+            shortfall: amount - heights[src - 1],
+        });
+    }
+    Ok(())
+}
+
+/// Generic over what's being moved rather than per method, so a crane can be picked at runtime
+/// as a `dyn Crane`
+pub trait Crane<T = String> {
+    fn apply_mut(&self, stacks: &mut Stacks<T>, instr: &Instruction);
+
+    fn apply(&self, mut stacks: Stacks<T>, instr: &Instruction) -> Stacks<T> {
+        self.apply_mut(&mut stacks, instr);
+        stacks
+    }
+
+    /// Like `apply`, but returns an error instead of panicking on a bad instruction
+    fn try_apply(
+        &self,
+        stacks: Stacks<T>,
+        index: usize,
+        instr: &Instruction,
+    ) -> Result<Stacks<T>, MoveError> {
+        stacks.check(index, instr)?;
+        Ok(self.apply(stacks, instr))
+    }
+}
+
+/// Moves crates one at a time
+pub struct CrateMover9000;
+
+impl<T> Crane<T> for CrateMover9000 {
+    fn apply_mut(&self, stacks: &mut Stacks<T>, instr: &Instruction) {
         match instr {
             Instruction::Move(amount, src, dest) => {
-                for _ in 0..*amount {
-                    let elem = self.rows[*src - 1]
-                        .pop()
-                        .expect("trying to move more elements than exist");
-                    self.rows[*dest - 1].push(elem);
+                if let Some(([from, to], start)) = stacks.lift(*amount, *src, *dest) {
+                    to.extend(from.drain(start..).rev());
                 }
-                self
             }
         }
     }
+}
+
+/// Moves every crate in an instruction at once, keeping their order
+pub struct CrateMover9001;
 
-    fn apply_part_two(mut self, instr: &Instruction) -> Self {
+impl<T> Crane<T> for CrateMover9001 {
+    fn apply_mut(&self, stacks: &mut Stacks<T>, instr: &Instruction) {
         match instr {
             Instruction::Move(amount, src, dest) => {
-                let src_vec_len = self.rows[*src - 1].len();
-                let copied = self.rows[*src - 1]
-                    .drain(src_vec_len - *amount..src_vec_len)
-                    .collect_vec();
-                for elem in copied {
-                    self.rows[*dest - 1].push(elem);
+                if let Some(([from, to], start)) = stacks.lift(*amount, *src, *dest) {
+                    to.extend(from.drain(start..));
                 }
-                self
             }
         }
     }
-    
-    fn get_top(&self) -> String {
-        self
+}
+
+/// Moves up to `capacity` crates at a time, keeping the order within each lift
+pub struct CappedCrane {
+    capacity: usize,
+}
+
+impl CappedCrane {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "crane must be able to lift at least one crate");
+        CappedCrane { capacity }
+    }
+}
+
+impl<T> Crane<T> for CappedCrane {
+    fn apply_mut(&self, stacks: &mut Stacks<T>, instr: &Instruction) {
+        match instr {
+            Instruction::Move(amount, src, dest) => {
+                let mut remaining = *amount;
+                while remaining > 0 {
+                    let lift = remaining.min(self.capacity);
+                    CrateMover9001.apply_mut(stacks, &Instruction::Move(lift, *src, *dest));
+                    remaining -= lift;
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Stacks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // every column is as wide as the widest crate or stack number
+        let width = self
             .rows
             .iter()
-            .filter_map(|x| x.last())
-            .map(|x| *x as char)
-            .collect()
+            .flatten()
+            .map(|label| label.chars().count() + 2)
+            .chain([3, self.rows.len().to_string().len()])
+            .max()
+            .unwrap();
+        let height = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        for level in (0..height).rev() {
+            let line = self
+                .rows
+                .iter()
+                .map(|row| match row.get(level) {
+                    Some(label) => format!("{:^width$}", format!("[{}]", label)),
+                    None => " ".repeat(width),
+                })
+                .join(" ");
+            writeln!(f, "{}", line)?;
+        }
+        write!(
+            f,
+            "{}",
+            (1..=self.rows.len())
+                .map(|n| format!("{:^width$}", n))
+                .join(" ")
+        )
     }
-    
+}
+
+/// Splits a line into its whitespace separated tokens along with the char columns they span
+fn tokens(line: &str) -> Vec<(usize, usize, String)> {
+    let mut tokens = vec![];
+    let mut current: Option<(usize, String)> = None;
+    for (col, chr) in line.chars().enumerate() {
+        match (chr.is_whitespace(), &mut current) {
+            (false, Some((_, token))) => token.push(chr),
+            (false, None) => current = Some((col, chr.to_string())),
+            (true, Some(_)) => {
+                let (start, token) = current.take().unwrap();
+                tokens.push((start, col - 1, token));
+            }
+            (true, None) => {}
+        }
+    }
+    if let Some((start, token)) = current {
+        tokens.push((start, start + token.chars().count() - 1, token));
+    }
+    tokens
 }
 
 impl FromStr for Stacks {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        fn transpose<T>(v: Vec<Vec<T>>) -> Vec<Vec<T>> {
-            assert!(!v.is_empty());
-            let len = v[0].len();
-            let mut iters: Vec<_> = v.into_iter().map(|n| n.into_iter()).collect();
-            (0..len)
-                .map(|_| {
-                    iters
-                        .iter_mut()
-                        .map(|n| n.next().unwrap())
-                        .rev()
-                        .collect::<Vec<T>>()
-                })
-                .collect()
-        }
-
-        let mut rows = vec![];
-        let num_lines = s.as_bytes().iter().filter(|x| **x == b'\n').count();
-        for i in s.split_inclusive('\n').take(num_lines) {
-            let mut row = vec![];
-            for chunk in i.as_bytes().array_chunks::<4>() {
-                if chunk.iter().take(3).all(|x| *x == b' ') {
-                    row.push(None)
-                } else {
-                    row.push(Some(chunk[1]))
+        let mut lines = s.lines().collect_vec();
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+        // the footer numbers tell us which columns each stack occupies
+        let footer = tokens(lines.pop().ok_or(())?);
+        for (n, (_, _, number)) in footer.iter().enumerate() {
+            if number.parse::<usize>() != Ok(n + 1) {
+                return Err(());
+            }
+        }
+
+        let mut rows = vec![vec![]; footer.len()];
+        for (level, line) in lines.iter().rev().enumerate() {
+            for (start, end, token) in tokens(line) {
+                let label = token
+                    .strip_prefix('[')
+                    .and_then(|token| token.strip_suffix(']'))
+                    .ok_or(())?;
+                let (stack,) = footer
+                    .iter()
+                    .positions(|(lo, hi, _)| *lo <= end && start <= *hi)
+                    .collect_tuple()
+                    .ok_or(())?;
+                // a crate can't float above an empty spot
+                if rows[stack].len() != level {
+                    return Err(());
                 }
+                rows[stack].push(label.to_string());
             }
-            rows.push(row);
         }
-        let rows = transpose(rows);
-        let rows = rows
-            .iter()
-            .map(|row| row.iter().filter_map(|x| *x).collect_vec())
-            .collect_vec();
-        Ok(Stacks { rows: rows })
+        Ok(Stacks { rows })
     }
 }
 
@@ -110,20 +301,195 @@ pub fn generator(input: &str) -> (Vec<Instruction>, Stacks) {
     (instrs, stacks)
 }
 
+pub fn solve(
+    crane: &(impl Crane + ?Sized),
+    (instrs, stacks): &(Vec<Instruction>, Stacks),
+) -> String {
+    let mut stacks = stacks.clone();
+    for instr in instrs {
+        crane.apply_mut(&mut stacks, instr);
+    }
+    stacks.get_top()
+}
+
+/// Validates the whole instruction list before running any of it
+pub fn try_solve(
+    crane: &(impl Crane + ?Sized),
+    input: &(Vec<Instruction>, Stacks),
+) -> Result<String, MoveError> {
+    let (instrs, stacks) = input;
+    stacks.dry_run(instrs)?;
+    Ok(solve(crane, input))
+}
+
+pub enum Goal {
+    /// The crates on top of each stack read this string
+    Top(String),
+    /// Every stack matches exactly
+    Arrangement(Stacks),
+}
+
+impl Goal {
+    fn is_met(&self, stacks: &Stacks) -> bool {
+        match self {
+            Goal::Top(top) => stacks.get_top() == *top,
+            Goal::Arrangement(target) => stacks == target,
+        }
+    }
+}
+
+/// Breadth first search for the shortest instruction list that reaches `goal`, giving up after `max_moves`
+pub fn plan(
+    crane: &(impl Crane + ?Sized),
+    stacks: &Stacks,
+    goal: &Goal,
+    max_moves: usize,
+) -> Option<Vec<Instruction>> {
+    let mut parents: HashMap<Stacks, Option<(Stacks, Instruction)>> = HashMap::new();
+    parents.insert(stacks.clone(), None);
+    let mut frontier = vec![stacks.clone()];
+    for depth in 0..=max_moves {
+        if let Some(found) = frontier.iter().find(|state| goal.is_met(state)) {
+            let mut instrs = vec![];
+            let mut current = found;
+            while let Some((parent, instr)) = &parents[current] {
+                instrs.push(*instr);
+                current = parent;
+            }
+            instrs.reverse();
+            return Some(instrs);
+        }
+        if depth == max_moves {
+            break;
+        }
+        let mut next = vec![];
+        for state in &frontier {
+            for instr in state.moves() {
+                let after = crane.apply(state.clone(), &instr);
+                if !parents.contains_key(&after) {
+                    parents.insert(after.clone(), Some((state.clone(), instr)));
+                    next.push(after);
+                }
+            }
+        }
+        frontier = next;
+    }
+    None
+}
+
+/// Writes the drawing before the first instruction and after every instruction
+pub fn animate(
+    crane: &(impl Crane + ?Sized),
+    (instrs, stacks): &(Vec<Instruction>, Stacks),
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    let mut stacks = stacks.clone();
+    writeln!(out, "{}", stacks)?;
+    for instr in instrs {
+        crane.apply_mut(&mut stacks, instr);
+        writeln!(out, "\n{}\n{}", instr, stacks)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CrateId(usize);
+
+/// A 1-based stack and the level within it, where 0 is the bottom
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Position {
+    pub stack: usize,
+    pub level: usize,
+}
+
+/// Where every crate was before the first instruction and after each one
+pub struct History {
+    labels: Vec<String>,
+    // positions[step][crate], where step 0 is the starting arrangement
+    positions: Vec<Vec<Position>>,
+    // tops[step][stack]
+    tops: Vec<Vec<Option<CrateId>>>,
+}
+
+impl History {
+    pub fn label(&self, id: CrateId) -> &str {
+        &self.labels[id.0]
+    }
+
+    /// Labels repeat, so this can be any number of crates
+    pub fn crates_labelled<'a>(&'a self, label: &'a str) -> impl Iterator<Item = CrateId> + 'a {
+        self.labels
+            .iter()
+            .positions(move |other| other == label)
+            .map(CrateId)
+    }
+
+    pub fn path(&self, id: CrateId) -> Vec<Position> {
+        self.positions.iter().map(|step| step[id.0]).collect()
+    }
+
+    /// The crate on top of the 1-based `stack` at every step
+    pub fn top_timeline(&self, stack: usize) -> Vec<Option<CrateId>> {
+        self.tops.iter().map(|step| step[stack - 1]).collect()
+    }
+
+    fn record(&mut self, ids: &Stacks<CrateId>) {
+        let mut positions = vec![Position { stack: 0, level: 0 }; self.labels.len()];
+        for (stack, row) in ids.rows.iter().enumerate() {
+            for (level, id) in row.iter().enumerate() {
+                positions[id.0] = Position {
+                    stack: stack + 1,
+                    level,
+                };
+            }
+        }
+        self.positions.push(positions);
+        self.tops
+            .push(ids.rows.iter().map(|row| row.last().copied()).collect());
+    }
+}
+
+/// Runs the instructions while following each crate individually
+pub fn track(
+    crane: &(impl Crane<CrateId> + ?Sized),
+    (instrs, stacks): &(Vec<Instruction>, Stacks),
+) -> History {
+    // crates are numbered stack by stack from the bottom up
+    let mut next_id = (0..).map(CrateId);
+    let mut ids = Stacks {
+        rows: stacks
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|_| next_id.next().unwrap()).collect())
+            .collect(),
+    };
+    let mut history = History {
+        labels: stacks.rows.iter().flatten().cloned().collect(),
+        positions: vec![],
+        tops: vec![],
+    };
+    history.record(&ids);
+    for instr in instrs {
+        crane.apply_mut(&mut ids, instr);
+        history.record(&ids);
+    }
+    history
+}
+
 #[aoc(day5, part1)]
-pub fn solve_part1((instrs, stacks): &(Vec<Instruction>, Stacks)) -> String {
-    instrs.into_iter().fold(stacks.clone(), |sum, next| sum.apply_part_one(next)).get_top()
+pub fn solve_part1(input: &(Vec<Instruction>, Stacks)) -> String {
+    solve(&CrateMover9000, input)
 }
 
 #[aoc(day5, part2)]
-pub fn solve_part2((instrs, stacks): &(Vec<Instruction>, Stacks)) -> String {
-    instrs.into_iter().fold(stacks.clone(), |sum, next| sum.apply_part_two(next)).get_top()
-
+pub fn solve_part2(input: &(Vec<Instruction>, Stacks)) -> String {
+    solve(&CrateMover9001, input)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test::Bencher;
 
     #[test]
     fn it_works_part1() {
@@ -160,4 +526,266 @@ move 1 from 1 to 2";
             solve_part2(&generator(include_str!("../input/2022/day5.txt")))
         );
     }
+
+    #[test]
+    fn capped_crane() {
+        let input = generator(EXAMPLE_INPUT);
+        assert_eq!("CMZ", solve(&CappedCrane::new(1), &input));
+        assert_eq!("MCZ", solve(&CappedCrane::new(2), &input));
+        assert_eq!("MCD", solve(&CappedCrane::new(3), &input));
+    }
+
+    #[test]
+    fn picks_crane_at_runtime() {
+        let input = generator(EXAMPLE_INPUT);
+        for (model, expected) in [("9000", "CMZ"), ("9001", "MCD"), ("capped-2", "MCZ")] {
+            let crane: Box<dyn Crane> = match model {
+                "9000" => Box::new(CrateMover9000),
+                "9001" => Box::new(CrateMover9001),
+                _ => Box::new(CappedCrane::new(2)),
+            };
+            assert_eq!(expected, solve(crane.as_ref(), &input));
+            assert_eq!(Ok(expected.to_string()), try_solve(crane.as_ref(), &input));
+        }
+    }
+
+    #[test]
+    fn validates_moves() {
+        let provided = "    [D]    
+[N] [C]    
+[Z] [M] [P]
+ 1   2   3
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 3 from 2 to 1
+move 1 from 4 to 2";
+        let input = generator(provided);
+        let not_enough = MoveError::NotEnoughCrates {
+            index: 2,
+            stack: 2,
+            shortfall: 1,
+        };
+        assert_eq!(Err(not_enough.clone()), input.1.dry_run(&input.0));
+        assert_eq!(Err(not_enough), try_solve(&CrateMover9001, &input));
+        assert_eq!(
+            Err(MoveError::NoSuchStack { index: 3, stack: 4 }),
+            input.1.check(3, &input.0[3])
+        );
+        assert!(CrateMover9000
+            .try_apply(input.1.clone(), 0, &input.0[0])
+            .is_ok());
+
+        // moving crates onto their own stack is allowed but leaves them where they were
+        let in_place = Instruction::Move(1, 3, 3);
+        assert_eq!(Ok(()), input.1.check(0, &in_place));
+        assert_eq!(input.1, CrateMover9000.apply(input.1.clone(), &in_place));
+        assert_eq!(input.1, CrateMover9001.apply(input.1.clone(), &in_place));
+        assert_eq!(
+            Err(MoveError::NotEnoughCrates {
+                index: 0,
+                stack: 3,
+                shortfall: 1
+            }),
+            input.1.check(0, &Instruction::Move(2, 3, 3))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "trying to move more elements than exist")]
+    fn rejects_short_moves_in_place() {
+        let (_, stacks) = generator(EXAMPLE_INPUT);
+        CrateMover9001.apply(stacks, &Instruction::Move(2, 3, 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "no such stack")]
+    fn rejects_stack_zero() {
+        let (_, stacks) = generator(EXAMPLE_INPUT);
+        CrateMover9000.apply(stacks, &Instruction::Move(1, 0, 1));
+    }
+
+    #[test]
+    fn draws_stacks() {
+        let drawing = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 ";
+        assert_eq!(drawing, drawing.parse::<Stacks>().unwrap().to_string());
+
+        let input = generator(&format!("{}\n\nmove 3 from 2 to 3", drawing));
+        let mut out = vec![];
+        animate(&CrateMover9000, &input, &mut out).unwrap();
+        assert_eq!(
+            format!(
+                "{}\n\nmove 3 from 2 to 3\n{}\n",
+                drawing, "        [M]\n        [C]\n[N]     [D]\n[Z]     [P]\n 1   2   3 "
+            ),
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_ragged_drawings() {
+        let trimmed = "    [D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3\n";
+        assert_eq!(
+            "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 ",
+            trimmed.parse::<Stacks>().unwrap().to_string()
+        );
+
+        let many = "[A]                                 [J]
+[B] [C] [D] [E] [F] [G] [H] [I] [K] [L]
+ 1   2   3   4   5   6   7   8   9  10 ";
+        let stacks = many.parse::<Stacks>().unwrap();
+        assert_eq!("ACDEFGHIKJ", stacks.get_top());
+        assert_eq!(many, stacks.to_string());
+
+        let wide = "       [Foo]\n[Ba]   [Q]\n  1     2";
+        let stacks = wide.parse::<Stacks>().unwrap();
+        assert_eq!("BaFoo", stacks.get_top());
+        assert_eq!("      [Foo]\n[Ba]   [Q] \n  1     2  ", stacks.to_string());
+        assert_eq!(stacks, stacks.to_string().parse().unwrap());
+
+        assert!("[A]\n    [B]\n 1   2".parse::<Stacks>().is_err());
+    }
+
+    #[test]
+    fn plans_moves() {
+        let stacks = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3"
+            .parse::<Stacks>()
+            .unwrap();
+        assert_eq!(
+            Some(vec![Instruction::Move(1, 2, 1)]),
+            plan(&CrateMover9001, &stacks, &Goal::Top("DCP".to_string()), 3)
+        );
+        assert_eq!(
+            Some(vec![]),
+            plan(&CrateMover9000, &stacks, &Goal::Top("NDP".to_string()), 3)
+        );
+        assert_eq!(
+            None,
+            plan(&CrateMover9000, &stacks, &Goal::Top("XYZ".to_string()), 2)
+        );
+
+        // reversing a stack takes one move with the 9000 but one per crate with the 9001
+        let reversed = CrateMover9000.apply(stacks.clone(), &Instruction::Move(3, 2, 3));
+        let goal = Goal::Arrangement(reversed);
+        assert_eq!(1, plan(&CrateMover9000, &stacks, &goal, 3).unwrap().len());
+        let instrs = plan(&CrateMover9001, &stacks, &goal, 3).unwrap();
+        assert_eq!(3, instrs.len());
+        assert!(goal.is_met(
+            &instrs
+                .iter()
+                .fold(stacks, |s, i| CrateMover9001.apply(s, i))
+        ));
+    }
+
+    #[test]
+    fn tracks_crates() {
+        let history = track(&CrateMover9000, &generator(EXAMPLE_INPUT));
+        let (d,) = history.crates_labelled("D").collect_tuple().unwrap();
+        let at = |stack, level| Position { stack, level };
+        assert_eq!(
+            vec![at(2, 2), at(1, 2), at(3, 1), at(3, 1), at(3, 1)],
+            history.path(d)
+        );
+        let tops = history
+            .top_timeline(1)
+            .into_iter()
+            .map(|id| id.map(|id| history.label(id)))
+            .collect_vec();
+        assert_eq!(vec![Some("N"), Some("D"), None, Some("M"), Some("C")], tops);
+    }
+
+    /// Nine stacks of 10k crates with 20k random (but valid) moves of up to 1k crates each, followed
+    /// by the moves undoing them. Every run leaves the stacks as it found them, so the benchmarks
+    /// never clone inside the timed loop
+    fn deep_input() -> (Vec<Instruction>, Stacks) {
+        let mut lcg = Lcg::new();
+        let mut next = |bound| lcg.below(bound);
+        let mut heights = [10_000; 9];
+        let stacks = Stacks {
+            rows: (b'A'..b'J')
+                .map(|label| vec![(label as char).to_string(); 10_000])
+                .collect(),
+        };
+        let moves = (0..20_000)
+            .map(|_| {
+                let (src, dest) = (next(9), next(9));
+                let amount = next(heights[src].min(1_000) + 1);
+                heights[src] -= amount;
+                heights[dest] += amount;
+                Instruction::Move(amount, src + 1, dest + 1)
+            })
+            .collect_vec();
+        // moving the same crates straight back undoes a move for either crane
+        let undo = moves
+            .iter()
+            .rev()
+            .map(|Instruction::Move(amount, src, dest)| Instruction::Move(*amount, *dest, *src));
+        let instrs = moves.iter().copied().chain(undo).collect();
+        (instrs, stacks)
+    }
+
+    #[bench]
+    fn bench_9000_in_place(b: &mut Bencher) {
+        let (instrs, mut stacks) = deep_input();
+        b.iter(|| {
+            for instr in &instrs {
+                CrateMover9000.apply_mut(&mut stacks, instr);
+            }
+            stacks.rows[0].len()
+        });
+    }
+
+    #[bench]
+    fn bench_9001_in_place(b: &mut Bencher) {
+        let (instrs, mut stacks) = deep_input();
+        b.iter(|| {
+            for instr in &instrs {
+                CrateMover9001.apply_mut(&mut stacks, instr);
+            }
+            stacks.rows[0].len()
+        });
+    }
+
+    /// The old one crate at a time approach, kept as a baseline for the 9000
+    #[bench]
+    fn bench_9000_pop_push(b: &mut Bencher) {
+        let (instrs, mut stacks) = deep_input();
+        b.iter(|| {
+            for Instruction::Move(amount, src, dest) in &instrs {
+                for _ in 0..*amount {
+                    let elem = stacks.rows[*src - 1].pop().unwrap();
+                    stacks.rows[*dest - 1].push(elem);
+                }
+            }
+            stacks.rows[0].len()
+        });
+    }
+
+    /// The old allocate-per-move approach, kept as a baseline for the 9001
+    #[bench]
+    fn bench_9001_drain_collect(b: &mut Bencher) {
+        let (instrs, mut stacks) = deep_input();
+        b.iter(|| {
+            for Instruction::Move(amount, src, dest) in &instrs {
+                let src_vec_len = stacks.rows[*src - 1].len();
+                let copied = stacks.rows[*src - 1]
+                    .drain(src_vec_len - *amount..src_vec_len)
+                    .collect_vec();
+                for elem in copied {
+                    stacks.rows[*dest - 1].push(elem);
+                }
+            }
+            stacks.rows[0].len()
+        });
+    }
+
+    const EXAMPLE_INPUT: &str = "    [D]    
+[N] [C]    
+[Z] [M] [P]
+ 1   2   3
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2";
 }
@@ -1,4 +1,7 @@
-use crate::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::io::{self, BufReader, Read};
+use std::iter;
 use std::str::FromStr;
 
 /// https://adventofcode.com/2022/day/6
@@ -7,25 +10,423 @@ pub fn generator(input: &str) -> String {
     input.to_string()
 }
 
+pub const PACKET_MARKER_LEN: usize = 4;
+pub const MESSAGE_MARKER_LEN: usize = 14;
+
+/// How many of each symbol a detector's window holds
+pub trait SymbolCounts<T>: Default {
+    /// Counts one more `symbol` and returns how many there are now
+    fn increment(&mut self, symbol: &T) -> usize;
+    /// Counts one fewer `symbol` and returns how many are left
+    fn decrement(&mut self, symbol: &T) -> usize;
+    fn clear(&mut self);
+}
+
+impl<T: Eq + Hash + Clone> SymbolCounts<T> for HashMap<T, usize> {
+    fn increment(&mut self, symbol: &T) -> usize {
+        let count = self.entry(symbol.clone()).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    fn decrement(&mut self, symbol: &T) -> usize {
+        let count = self.get_mut(symbol).unwrap();
+        *count -= 1;
+        let left = *count;
+        if left == 0 {
+            self.remove(symbol);
+        }
+        left
+    }
+
+    fn clear(&mut self) {
+        HashMap::clear(self);
+    }
+}
+
+/// A count for every possible byte, so bytes never get hashed
+pub struct ByteCounts([usize; 256]);
+
+impl Default for ByteCounts {
+    fn default() -> Self {
+        ByteCounts([0; 256])
+    }
+}
+
+impl SymbolCounts<u8> for ByteCounts {
+    fn increment(&mut self, symbol: &u8) -> usize {
+        self.0[*symbol as usize] += 1;
+        self.0[*symbol as usize]
+    }
+
+    fn decrement(&mut self, symbol: &u8) -> usize {
+        self.0[*symbol as usize] -= 1;
+        self.0[*symbol as usize]
+    }
+
+    fn clear(&mut self) {
+        self.0 = [0; 256];
+    }
+}
+
+/// Counts references the same way as the symbols they point to
+#[derive(Default)]
+pub struct RefCounts<C>(C);
+
+impl<'a, T, C: SymbolCounts<T>> SymbolCounts<&'a T> for RefCounts<C> {
+    fn increment(&mut self, symbol: &&'a T) -> usize {
+        self.0.increment(symbol)
+    }
+
+    fn decrement(&mut self, symbol: &&'a T) -> usize {
+        self.0.decrement(symbol)
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Anything markers can be made of, along with how to count it. Bytes use a flat array, and any
+/// other hashable token can be wrapped in `Hashed`
+pub trait Symbol: Clone {
+    type Counts: SymbolCounts<Self>;
+}
+
+impl Symbol for u8 {
+    type Counts = ByteCounts;
+}
+
+impl<T: Symbol> Symbol for &T {
+    type Counts = RefCounts<T::Counts>;
+}
+
+/// Any `Eq + Hash` token, counted in a `HashMap`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hashed<T>(pub T);
+
+impl<T: Eq + Hash + Clone> Symbol for Hashed<T> {
+    type Counts = HashMap<Hashed<T>, usize>;
+}
+
+impl Symbol for char {
+    type Counts = HashMap<char, usize>;
+}
+
+impl Symbol for String {
+    type Counts = HashMap<String, usize>;
+}
+
+impl<'a> Symbol for &'a str {
+    type Counts = HashMap<&'a str, usize>;
+}
+
+/// Tracks whether the last `size` symbols were all distinct. Symbols can be bytes, chars or any
+/// other token, and memory stays bounded by the window size
+pub struct MarkerDetector<T: Symbol = u8> {
+    size: usize,
+    window: VecDeque<T>,
+    counts: T::Counts,
+    // how many symbols show up more than once in the window
+    duplicates: usize,
+}
+
+impl<T: Symbol> MarkerDetector<T> {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "markers need a window of at least one symbol");
+        MarkerDetector {
+            size,
+            window: VecDeque::with_capacity(size),
+            counts: T::Counts::default(),
+            duplicates: 0,
+        }
+    }
+
+    /// Slides the window along by one symbol and returns whether it now holds a marker
+    pub fn push(&mut self, symbol: T) -> bool {
+        if self.window.len() == self.size {
+            let old = self.window.pop_front().unwrap();
+            if self.counts.decrement(&old) == 1 {
+                self.duplicates -= 1;
+            }
+        }
+        if self.counts.increment(&symbol) == 2 {
+            self.duplicates += 1;
+        }
+        self.window.push_back(symbol);
+        self.window.len() == self.size && self.duplicates == 0
+    }
+
+    pub fn reset(&mut self) {
+        self.window.clear();
+        self.counts.clear();
+        self.duplicates = 0;
+    }
+}
+
+/// Every position just past a window of `n` distinct symbols, in order
+pub fn find_markers<T: Symbol>(
+    input: impl IntoIterator<Item = T>,
+    n: usize,
+) -> impl Iterator<Item = usize> {
+    let mut detector = MarkerDetector::new(n);
+    input
+        .into_iter()
+        .enumerate()
+        .filter_map(move |(idx, symbol)| detector.push(symbol).then_some(idx + 1))
+}
+
+/// A set of bytes packed into 256 bits
+#[derive(Default, Copy, Clone)]
+struct ByteMask([u64; 4]);
+
+impl ByteMask {
+    fn toggle(&mut self, byte: u8) {
+        self.0[(byte >> 6) as usize] ^= 1 << (byte & 63);
+    }
+
+    fn contains(&self, byte: u8) -> bool {
+        self.0[(byte >> 6) as usize] & 1 << (byte & 63) != 0
+    }
+}
+
+/// Which bytes have been toggled an odd number of times. Each byte gets its own flag rather than a
+/// bit, so toggling never waits on its neighbours, and the set ones are counted as they change
+struct ByteParity {
+    odd: [bool; 256],
+    len: usize,
+}
+
+impl Default for ByteParity {
+    fn default() -> Self {
+        ByteParity {
+            odd: [false; 256],
+            len: 0,
+        }
+    }
+}
+
+impl ByteParity {
+    fn toggle(&mut self, byte: u8) {
+        let odd = &mut self.odd[byte as usize];
+        *odd = !*odd;
+        // one more if it's now set, one fewer if it was cleared
+        self.len = self.len + 2 * usize::from(*odd) - 1;
+    }
+}
+
+/// Byte-only version of `find_markers`. Pairs of duplicates cancel out when XORed together, so a
+/// window is a marker exactly when it leaves `n` bytes with odd parity
+pub fn find_markers_xor(input: &[u8], n: usize) -> impl Iterator<Item = usize> + '_ {
+    assert!(n > 0, "markers need a window of at least one byte");
+    let mut parity = ByteParity::default();
+    input.iter().enumerate().filter_map(move |(idx, byte)| {
+        parity.toggle(*byte);
+        if idx >= n {
+            parity.toggle(input[idx - n]);
+        }
+        (idx + 1 >= n && parity.len == n).then_some(idx + 1)
+    })
+}
+
+/// Finds the first marker by checking each window from its end. When a duplicate turns up, no
+/// window holding both copies can be a marker, so we skip straight past the earlier one
+pub fn find_first_marker(input: &[u8], n: usize) -> Option<usize> {
+    assert!(n > 0, "markers need a window of at least one byte");
+    let mut start = 0;
+    'windows: while start + n <= input.len() {
+        let mut seen = ByteMask::default();
+        for pos in (start..start + n).rev() {
+            if seen.contains(input[pos]) {
+                start = pos + 1;
+                continue 'windows;
+            }
+            seen.toggle(input[pos]);
+        }
+        return Some(start + n);
+    }
+    None
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Marker {
+    StartOfPacket(usize),
+    StartOfMessage(usize),
+}
+
+/// Reports the first start-of-packet and start-of-message markers as soon as their last symbol arrives
+pub struct MarkerScanner<T: Symbol = u8> {
+    offset: usize,
+    packet: Option<MarkerDetector<T>>,
+    message: Option<MarkerDetector<T>>,
+}
+
+impl<T: Symbol> MarkerScanner<T> {
+    pub fn new() -> Self {
+        MarkerScanner {
+            offset: 0,
+            packet: Some(MarkerDetector::new(PACKET_MARKER_LEN)),
+            message: Some(MarkerDetector::new(MESSAGE_MARKER_LEN)),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.packet.is_none() && self.message.is_none()
+    }
+
+    pub fn push(&mut self, symbol: T) -> Option<Marker> {
+        self.offset += 1;
+        let packet = self
+            .packet
+            .as_mut()
+            .is_some_and(|d| d.push(symbol.clone()));
+        let message = self.message.as_mut().is_some_and(|d| d.push(symbol));
+        // a message marker contains a packet marker that ended earlier, so at most one of these is set
+        if packet {
+            self.packet = None;
+            Some(Marker::StartOfPacket(self.offset))
+        } else if message {
+            self.message = None;
+            Some(Marker::StartOfMessage(self.offset))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: Symbol> Default for MarkerScanner<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Scans symbols lazily, stopping as soon as both markers have been found
+pub fn scan<T: Symbol>(
+    symbols: impl IntoIterator<Item = T>,
+) -> impl Iterator<Item = Marker> {
+    let mut scanner = MarkerScanner::new();
+    let mut symbols = symbols.into_iter();
+    iter::from_fn(move || {
+        while !scanner.is_done() {
+            if let Some(marker) = scanner.push(symbols.next()?) {
+                return Some(marker);
+            }
+        }
+        None
+    })
+}
+
+/// Like `scan`, but pulls a byte datastream from a reader
+pub fn scan_reader(reader: impl Read) -> impl Iterator<Item = io::Result<Marker>> {
+    let mut scanner = MarkerScanner::new();
+    let mut bytes = BufReader::new(reader).bytes();
+    iter::from_fn(move || {
+        while !scanner.is_done() {
+            match bytes.next()? {
+                Ok(byte) => {
+                    if let Some(marker) = scanner.push(byte) {
+                        return Some(Ok(marker));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    })
+}
+
+/// The symbols between one marker and the next, along with the offset they start at
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Frame<'a, T = u8> {
+    pub offset: usize,
+    pub payload: &'a [T],
+}
+
+/// Splits a datastream into frames. Anything before the first marker is dropped, and markers never
+/// overlap each other so payload symbols can't be mistaken for the start of the next marker
+pub struct Frames<'a, T: Symbol = u8> {
+    input: &'a [T],
+    marker_len: usize,
+    detector: MarkerDetector<T>,
+    pos: usize,
+    start: Option<usize>,
+}
+
+impl<'a, T: Symbol> Frames<'a, T> {
+    pub fn new(input: &'a [T], marker_len: usize) -> Self {
+        Frames {
+            input,
+            marker_len,
+            detector: MarkerDetector::new(marker_len),
+            pos: 0,
+            start: None,
+        }
+    }
+
+    fn next_marker(&mut self) -> Option<usize> {
+        while self.pos < self.input.len() {
+            self.pos += 1;
+            if self.detector.push(self.input[self.pos - 1].clone()) {
+                self.detector.reset();
+                return Some(self.pos);
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T: Symbol> Iterator for Frames<'a, T> {
+    type Item = Frame<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = match self.start {
+            Some(start) => start,
+            None => self.next_marker()?,
+        };
+        self.start = self.next_marker();
+        let end = match self.start {
+            Some(next) => next - self.marker_len,
+            None => self.input.len(),
+        };
+        Some(Frame {
+            offset: start,
+            payload: &self.input[start..end],
+        })
+    }
+}
+
+pub fn packets<T: Symbol>(input: &[T]) -> Frames<'_, T> {
+    Frames::new(input, PACKET_MARKER_LEN)
+}
+
+pub fn messages<T: Symbol>(input: &[T]) -> Frames<'_, T> {
+    Frames::new(input, MESSAGE_MARKER_LEN)
+}
+
+fn first_marker(input: &str, n: usize) -> usize {
+    // ascii bytes and chars line up so the byte fast path gives the same answer
+    if input.is_ascii() {
+        find_first_marker(input.as_bytes(), n).unwrap()
+    } else {
+        find_markers(input.chars(), n).next().unwrap()
+    }
+}
+
 #[aoc(day6, part1)]
 pub fn solve_part1(input: &str) -> usize {
-    let (idx, _) = input.as_bytes().array_windows::<4>().enumerate().find(|(idx, chunk)| {
-        chunk.iter().unique().count() == 4
-    }).unwrap();
-    idx + 4
+    first_marker(input, PACKET_MARKER_LEN)
 }
 
 #[aoc(day6, part2)]
 pub fn solve_part2(input: &str) -> usize {
-    let (idx, _) = input.as_bytes().array_windows::<14>().enumerate().find(|(idx, chunk)| {
-        chunk.iter().unique().count() == 14
-    }).unwrap();
-    idx + 14
+    first_marker(input, MESSAGE_MARKER_LEN)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::prelude::*;
+    use test::Bencher;
 
     #[test]
     fn it_works_part1() {
@@ -53,4 +454,136 @@ mod tests {
                 solve_part2(&generator(include_str!("../input/2022/day6.txt")))
             );
         }
+
+    #[test]
+    fn finds_every_marker() {
+        assert_eq!(vec![3, 4, 5, 6], find_markers(b"abcabd", 3).collect_vec());
+        assert_eq!(vec![3, 4, 6], find_markers(b"aabaab", 2).collect_vec());
+        let input = b"nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg";
+        for n in 1..=16 {
+            let naive = input
+                .windows(n)
+                .enumerate()
+                .filter(|(_, window)| window.iter().unique().count() == n)
+                .map(|(idx, _)| idx + n)
+                .collect_vec();
+            assert_eq!(naive, find_markers(input, n).collect_vec());
+        }
+    }
+
+    #[test]
+    fn scans_streams() {
+        let stream = b"mjqjpqmgbljsphdztnvjfqwrcgsmlb";
+        let expected = vec![Marker::StartOfPacket(7), Marker::StartOfMessage(19)];
+        // the scan has to stop once both markers are found or this would never end
+        let endless = stream.iter().copied().chain(iter::repeat(b'a'));
+        assert_eq!(expected, scan(endless).collect_vec());
+        assert_eq!(
+            expected,
+            scan_reader(&stream[..]).collect::<io::Result<Vec<_>>>().unwrap()
+        );
+        assert_eq!(
+            vec![Marker::StartOfPacket(4)],
+            scan(b"abcdabcd".iter().copied()).collect_vec()
+        );
+    }
+
+    #[test]
+    fn splits_frames() {
+        let frame = |offset, payload| Frame { offset, payload };
+        assert_eq!(
+            vec![frame(8, &b"zzz"[..]), frame(15, &b"zqq"[..])],
+            packets(b"aaaaabcdzzzzwxyzqq").collect_vec()
+        );
+        assert_eq!(
+            vec![frame(19, &b"jfqwrcgsmlb"[..])],
+            messages(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb").collect_vec()
+        );
+        assert_eq!(0, packets(b"aaaa").count());
+    }
+
+    #[test]
+    fn finds_markers_in_any_alphabet() {
+        // the é and è share a leading byte so a byte based search gets these wrong
+        assert_eq!(vec![4, 5], find_markers("éèéaè".chars(), 3).collect_vec());
+        assert_eq!(
+            vec![Marker::StartOfPacket(5)],
+            scan("ααβγδα".chars()).collect_vec()
+        );
+        let words = "the cat sat on the mat".split(' ').collect_vec();
+        assert_eq!(vec![3, 4, 5, 6], find_markers(words.iter(), 3).collect_vec());
+        assert_eq!(
+            vec![Frame {
+                offset: 4,
+                payload: &words[4..]
+            }],
+            packets(&words).collect_vec()
+        );
+
+        let tokens = vec![1u32, 2, 1, 300, 2];
+        assert_eq!(
+            vec![4, 5],
+            find_markers(tokens.into_iter().map(Hashed), 3).collect_vec()
+        );
+        assert_eq!(
+            vec![Marker::StartOfPacket(4)],
+            scan([(1, 'a'), (2, 'a'), (1, 'b'), (2, 'b')].map(Hashed)).collect_vec()
+        );
+    }
+
+    #[test]
+    fn fast_paths_agree() {
+        let input = b"nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg\xff\x00\xfe\x80";
+        for n in 1..=16 {
+            let expected = find_markers(input, n).collect_vec();
+            assert_eq!(expected, find_markers_xor(input, n).collect_vec());
+            assert_eq!(expected.first().copied(), find_first_marker(input, n));
+        }
+        assert_eq!(19, solve_part2("mjqjpqmgbljsphdztnvjfqwrcgsmlb"));
+        assert_eq!(5, solve_part1("ééaèb"));
+    }
+
+    /// 256k random bytes drawn from 13 letters, so the only 14 byte marker is tacked on the end
+    fn random_input() -> Vec<u8> {
+        let mut lcg = Lcg::new();
+        let mut input = (0..1 << 18)
+            .map(|_| b'a' + lcg.below(13) as u8)
+            .collect_vec();
+        input.extend(b"nopqrstuvwxyz");
+        input
+    }
+
+    #[bench]
+    fn bench_first_marker_unique(b: &mut Bencher) {
+        let input = random_input();
+        b.iter(|| {
+            input
+                .windows(MESSAGE_MARKER_LEN)
+                .position(|window| window.iter().unique().count() == MESSAGE_MARKER_LEN)
+        });
+    }
+
+    #[bench]
+    fn bench_first_marker_detector(b: &mut Bencher) {
+        let input = random_input();
+        b.iter(|| find_markers(&input, MESSAGE_MARKER_LEN).next());
+    }
+
+    #[bench]
+    fn bench_first_marker_skip(b: &mut Bencher) {
+        let input = random_input();
+        b.iter(|| find_first_marker(&input, MESSAGE_MARKER_LEN));
+    }
+
+    #[bench]
+    fn bench_all_markers_detector(b: &mut Bencher) {
+        let input = random_input();
+        b.iter(|| find_markers(&input, PACKET_MARKER_LEN).count());
+    }
+
+    #[bench]
+    fn bench_all_markers_xor(b: &mut Bencher) {
+        let input = random_input();
+        b.iter(|| find_markers_xor(&input, PACKET_MARKER_LEN).count());
+    }
 }
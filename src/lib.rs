@@ -1,16 +1,20 @@
 #![feature(array_windows)]
 #![feature(array_chunks)]
+#![cfg_attr(test, feature(test))]
+#[cfg(test)]
+extern crate test;
+
 mod prelude;
 mod day1;
-mod day2;
-mod day3;
-mod day4;
-mod day5;
-mod day6;
+pub mod day2;
+pub mod day3;
+pub mod day4;
+pub mod day5;
+pub mod day6;
 mod day7;
 mod day8;
 mod day9;
-mod day10;
+pub mod day10;
 
 
 #[macro_use]
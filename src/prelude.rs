@@ -1,3 +1,23 @@
 pub use itertools::Itertools;
 pub use std::collections::HashSet;
 pub use parse_display::{Display, FromStr};
+
+/// Fixed LCG so every run benchmarks the same input
+#[cfg(test)]
+pub struct Lcg(u64);
+
+#[cfg(test)]
+impl Lcg {
+    pub fn new() -> Self {
+        Lcg(0x2545f4914f6cdd1d)
+    }
+
+    /// The next number in `0..bound`
+    pub fn below(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) as usize % bound
+    }
+}